@@ -1,8 +1,10 @@
 pub use crate::inner::crypto_kitties as cryptokitties;
 pub use crate::inner::crypto_punks as cryptopunks;
 pub use crate::inner::erc_1155 as erc1155;
+pub use crate::inner::erc_1271 as erc1271;
 pub use crate::inner::erc_165 as erc165;
 pub use crate::inner::erc_20 as erc20;
+pub use crate::inner::erc_6093 as erc6093;
 pub use crate::inner::erc_721 as erc721;
 pub use crate::inner::weth;
 
@@ -13,8 +15,10 @@ mod inner {
     abigen!(CryptoKitties, "src/contracts-abi/abi/cryptokitties.json");
     abigen!(CryptoPunks, "src/contracts-abi/abi/cryptopunks.json");
     abigen!(Erc1155, "src/contracts-abi/abi/erc1155.json");
+    abigen!(Erc1271, "src/contracts-abi/abi/erc1271.json");
     abigen!(Erc165, "src/contracts-abi/abi/erc165.json");
     abigen!(Erc20, "src/contracts-abi/abi/erc20.json");
+    abigen!(Erc6093, "src/contracts-abi/abi/erc6093.json");
     abigen!(Erc721, "src/contracts-abi/abi/erc721.json");
     abigen!(Weth, "src/contracts-abi/abi/weth.json");
 }
@@ -0,0 +1,50 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+
+use crate::stable_memory::{init_stable_cell_default, Candid, StableCell, ADMINS_ID};
+
+thread_local! {
+    static ADMINS: RefCell<StableCell<Candid<Vec<Principal>>>> = RefCell::new(init_stable_cell_default(ADMINS_ID));
+}
+
+/// Guard for the methods that can only be called by the controllers or the admins.
+pub(crate) fn caller_is_admin() -> Result<(), String> {
+    let caller = ic_cdk::caller();
+
+    if is_admin(&caller) {
+        Ok(())
+    } else {
+        Err(format!("{caller} is not allowed to call this method"))
+    }
+}
+
+pub(crate) fn is_admin(principal: &Principal) -> bool {
+    ic_cdk::api::is_controller(principal) || admins().contains(principal)
+}
+
+/// Admins explicitly added to the canister, controllers are admins implicitly.
+pub(crate) fn admins() -> Vec<Principal> {
+    ADMINS.with(|admins| admins.borrow().get().0.clone())
+}
+
+pub(crate) fn add_admin(principal: Principal) {
+    update_admins(|admins| {
+        if !admins.contains(&principal) {
+            admins.push(principal);
+        }
+    });
+}
+
+pub(crate) fn remove_admin(principal: Principal) {
+    update_admins(|admins| admins.retain(|admin| admin != &principal));
+}
+
+fn update_admins(f: impl FnOnce(&mut Vec<Principal>)) {
+    ADMINS.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let mut admins = cell.get().0.clone();
+        f(&mut admins);
+        cell.set(Candid(admins)).expect("failed to save admins");
+    });
+}
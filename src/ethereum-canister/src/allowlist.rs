@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use candid::Principal;
+use ethers_core::types::Address;
+use eyre::Result;
+
+use crate::access_control::is_admin;
+use crate::stable_memory::{
+    init_stable_cell_default, Candid, StableCell, CALLER_ALLOWLIST_ID, CONTRACT_ALLOWLIST_ID,
+};
+
+thread_local! {
+    // None means that everyone is allowed
+    static CALLER_ALLOWLIST: RefCell<StableCell<Candid<Option<BTreeSet<Principal>>>>> = RefCell::new(init_stable_cell_default(CALLER_ALLOWLIST_ID));
+    static CONTRACT_ALLOWLIST: RefCell<StableCell<Candid<Option<BTreeSet<interface::Address>>>>> = RefCell::new(init_stable_cell_default(CONTRACT_ALLOWLIST_ID));
+}
+
+/// Returned when the caller or the contract is not in the allowlist.
+#[derive(Debug)]
+pub(crate) struct NotAllowed(pub(crate) String);
+
+impl fmt::Display for NotAllowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotAllowed {}
+
+pub(crate) fn caller_allowlist() -> Option<Vec<Principal>> {
+    CALLER_ALLOWLIST.with(|cell| {
+        let allowlist = &cell.borrow().get().0;
+        allowlist.as_ref().map(|set| set.iter().copied().collect())
+    })
+}
+
+/// Sets the principals allowed to use the canister, `None` allows everyone.
+pub(crate) fn set_caller_allowlist(principals: Option<Vec<Principal>>) {
+    let allowlist = principals.map(BTreeSet::from_iter);
+    CALLER_ALLOWLIST.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(allowlist))
+            .expect("failed to save caller allowlist")
+    });
+}
+
+pub(crate) fn contract_allowlist() -> Option<Vec<interface::Address>> {
+    CONTRACT_ALLOWLIST.with(|cell| {
+        let allowlist = &cell.borrow().get().0;
+        allowlist.as_ref().map(|set| set.iter().copied().collect())
+    })
+}
+
+/// Sets the contracts that can be called, `None` allows all of them.
+pub(crate) fn set_contract_allowlist(contracts: Option<Vec<interface::Address>>) {
+    let allowlist = contracts.map(BTreeSet::from_iter);
+    CONTRACT_ALLOWLIST.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(allowlist))
+            .expect("failed to save contract allowlist")
+    });
+}
+
+/// Fails if the caller is not in the allowlist, admins are always allowed.
+pub(crate) fn ensure_caller_allowed() -> Result<()> {
+    let caller = ic_cdk::caller();
+    let allowed = CALLER_ALLOWLIST.with(|cell| match &cell.borrow().get().0 {
+        Some(allowlist) => allowlist.contains(&caller),
+        None => true,
+    });
+
+    if allowed || is_admin(&caller) {
+        Ok(())
+    } else {
+        Err(NotAllowed(format!("Caller {caller} is not in the allowlist")).into())
+    }
+}
+
+/// Fails if the caller or the contract is not in the allowlist.
+pub(crate) fn ensure_allowed(contract: Address) -> Result<()> {
+    ensure_caller_allowed()?;
+
+    let allowed = CONTRACT_ALLOWLIST.with(|cell| match &cell.borrow().get().0 {
+        Some(allowlist) => allowlist.contains(&contract.into()),
+        None => true,
+    });
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(NotAllowed(format!("Contract {contract:?} is not in the allowlist")).into())
+    }
+}
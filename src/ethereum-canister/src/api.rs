@@ -0,0 +1,175 @@
+//! Implementation of the endpoints, shared by the panicking and the `*_result` variants.
+
+use ethers_core::types::{Address as EthersAddress, Signature as EthersSignature, U256};
+use eyre::{eyre, Result};
+use interface::{
+    Address, CallCost, CheckTokenGateRequest, Erc1271IsValidSignatureRequest,
+    Erc20BalanceOfRequest, Erc721OwnerOfRequest, EstimateCallCostRequest, EstimateGasRequest,
+    RecoverAddressRequest, SignTypedDataRequest, SiweLoginRequest,
+};
+
+use crate::metering::{self, Metered};
+use crate::revert::Revert;
+use crate::stable_memory::load_static_string;
+use crate::utils::IntoCallOpts;
+use crate::{
+    allowlist, ecdsa, erc1271, erc20, erc721, finality, helios, pricing, quotas, signature, siwe,
+    staleness, token_gate,
+};
+use crate::{load_network, ECDSA_KEY_NAME, SIWE_DOMAIN};
+
+pub(crate) async fn get_block_number() -> Result<u64> {
+    staleness::ensure_fresh().await?;
+    finality::get_block_number().await
+}
+
+pub(crate) async fn get_gas_price() -> Result<U256> {
+    staleness::ensure_fresh().await?;
+    finality::get_gas_price().await
+}
+
+pub(crate) async fn estimate_gas(request: EstimateGasRequest) -> Result<U256> {
+    finality::ensure_latest_allowed()?;
+    allowlist::ensure_allowed(request.to.into())?;
+
+    quotas::limit(pricing::charge(async {
+        staleness::ensure_fresh().await?;
+
+        let opts = &request.into_call_opts();
+        helios::execute(|client| async move { client.estimate_gas(opts).await }).await
+    }))
+    .await
+}
+
+pub(crate) async fn erc20_balance_of(request: Erc20BalanceOfRequest) -> Result<U256> {
+    allowlist::ensure_allowed(request.contract.into())?;
+
+    quotas::limit(pricing::charge(erc20::balance_of(
+        request.contract.into(),
+        request.account.into(),
+    )))
+    .await
+}
+
+pub(crate) async fn erc721_owner_of(request: Erc721OwnerOfRequest) -> Result<EthersAddress> {
+    allowlist::ensure_allowed(request.contract.into())?;
+
+    quotas::limit(pricing::charge(erc721::owner_of(
+        request.contract.into(),
+        request.token_id.into(),
+    )))
+    .await
+}
+
+pub(crate) async fn erc1271_is_valid_signature(
+    request: Erc1271IsValidSignatureRequest,
+) -> Result<bool> {
+    allowlist::ensure_allowed(request.contract.into())?;
+
+    quotas::limit(pricing::charge(erc1271::is_valid_signature(
+        request.contract.into(),
+        request.hash.into(),
+        request.signature,
+    )))
+    .await
+}
+
+pub(crate) async fn sign_typed_data(request: SignTypedDataRequest) -> Result<EthersSignature> {
+    allowlist::ensure_caller_allowed()?;
+
+    let key_name = load_static_string(&ECDSA_KEY_NAME)
+        .unwrap_or_else(|| ecdsa::DEFAULT_ECDSA_KEY_NAME.to_owned());
+    let derivation_path = ecdsa::caller_derivation_path(ic_cdk::caller(), request.derivation_path);
+
+    quotas::limit(pricing::charge(ecdsa::sign_typed_data(
+        key_name,
+        derivation_path,
+        &request.typed_data_json,
+    )))
+    .await
+}
+
+pub(crate) fn recover_address(request: RecoverAddressRequest) -> Result<EthersAddress> {
+    signature::recover_address(request.message_hash.into(), &request.signature.into())
+}
+
+pub(crate) async fn siwe_prepare(address: Address) -> Result<String> {
+    allowlist::ensure_caller_allowed()?;
+    let (domain, chain_id) = siwe_config()?;
+
+    quotas::limit(metering::meter(siwe::prepare(
+        ic_cdk::caller(),
+        address.into(),
+        &domain,
+        chain_id,
+    )))
+    .await
+}
+
+pub(crate) async fn siwe_login(request: SiweLoginRequest) -> Result<EthersAddress> {
+    allowlist::ensure_caller_allowed()?;
+    let (domain, chain_id) = siwe_config()?;
+
+    quotas::limit(pricing::charge(siwe::login(
+        ic_cdk::caller(),
+        &request.message,
+        &request.signature,
+        &domain,
+        chain_id,
+    )))
+    .await
+}
+
+pub(crate) async fn check_token_gate(request: CheckTokenGateRequest) -> Result<bool> {
+    allowlist::ensure_caller_allowed()?;
+    token_gate::validate(&request.rule)?;
+    for contract in token_gate::contracts(&request.rule) {
+        allowlist::ensure_allowed(contract)?;
+    }
+
+    let Some(account) = siwe::linked_address(request.principal) else {
+        return Ok(false);
+    };
+
+    quotas::limit(pricing::charge(token_gate::evaluate(
+        &request.rule,
+        account,
+    )))
+    .await
+}
+
+pub(crate) async fn estimate_call_cost(request: EstimateCallCostRequest) -> Result<CallCost> {
+    allowlist::ensure_allowed(request.contract.into())?;
+
+    // the dry run makes the same outcalls as the call, it's charged what they cost
+    // and doesn't fill the cache, so that the call makes them again
+    quotas::limit(pricing::charge_at_cost(async {
+        let Metered { result, usage } = metering::meter(helios::call_raw_uncached(
+            request.contract.into(),
+            request.data,
+        ))
+        .await;
+        match result {
+            // reverted calls are charged as well
+            Err(err) if !err.is::<Revert>() => return Err(err),
+            _ => {}
+        }
+
+        Ok(CallCost {
+            estimated_outcalls: usage.outcalls,
+            outcalls_cycles: usage.cycles,
+            cycles_to_attach: pricing::price_list().map(|price_list| {
+                pricing::price(&price_list, usage.outcalls).max(price_list.min_attached_cycles)
+            }),
+        })
+    }))
+    .await
+}
+
+fn siwe_config() -> Result<(String, u64)> {
+    let domain =
+        load_static_string(&SIWE_DOMAIN).ok_or_else(|| eyre!("SIWE domain not configured"))?;
+    let network = load_network().ok_or_else(|| eyre!("Network not configured"))?;
+
+    Ok((domain, network.chain_id()))
+}
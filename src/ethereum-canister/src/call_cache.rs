@@ -0,0 +1,54 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use ethers_core::types::{Address, H256};
+
+/// Upper bound of the size of the cached call data and results, further results of the block are not cached.
+const MAX_BYTES: usize = 4 * 1024 * 1024;
+
+thread_local! {
+    static CACHE: RefCell<CallCache> = RefCell::new(CallCache::default());
+}
+
+/// Results of the calls executed on a single block.
+///
+/// The block is identified by its hash, so the results of a block that was reorged out
+/// are never served. Once a call is made on another block the cache is cleared.
+#[derive(Default)]
+struct CallCache {
+    block_hash: H256,
+    results: HashMap<(Address, Vec<u8>), Vec<u8>>,
+    bytes: usize,
+}
+
+/// Result of the call if it was already executed on the block.
+pub(crate) fn get(block_hash: H256, contract: Address, call_data: &[u8]) -> Option<Vec<u8>> {
+    CACHE.with(|cache| {
+        let cache = cache.borrow();
+        if cache.block_hash != block_hash {
+            return None;
+        }
+        cache.results.get(&(contract, call_data.to_vec())).cloned()
+    })
+}
+
+/// Caches the result of the call executed on the block, which must be the current head.
+pub(crate) fn insert(block_hash: H256, contract: Address, call_data: Vec<u8>, result: Vec<u8>) {
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.block_hash != block_hash {
+            cache.block_hash = block_hash;
+            cache.results.clear();
+            cache.bytes = 0;
+        }
+
+        let key_size = call_data.len();
+        let size = key_size + result.len();
+        if cache.bytes + size <= MAX_BYTES {
+            if let Some(replaced) = cache.results.insert((contract, call_data), result) {
+                cache.bytes -= key_size + replaced.len();
+            }
+            cache.bytes += size;
+        }
+    });
+}
@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str;
+
+use ethers_core::types::H256;
+use eyre::{bail, eyre, Result, WrapErr};
+use helios_common::http;
+use log::warn;
+use serde_json::Value;
+
+use crate::stable_memory::{
+    init_stable_cell_default, load_static_strings, save_static_strings, Candid, StableCell,
+    CHECKPOINT_QUORUM_ID, CHECKPOINT_SYNC_URLS_ID,
+};
+
+thread_local! {
+    static CHECKPOINT_SYNC_URLS: RefCell<StableCell<Candid<Vec<String>>>> = RefCell::new(init_stable_cell_default(CHECKPOINT_SYNC_URLS_ID));
+    static CHECKPOINT_QUORUM: RefCell<StableCell<Candid<Option<u32>>>> = RefCell::new(init_stable_cell_default(CHECKPOINT_QUORUM_ID));
+}
+
+/// Saves the checkpoint sync providers and the quorum used for all the later checkpoint fetches.
+pub(crate) fn set_sync_config(checkpoint_sync_urls: Vec<String>, quorum: Option<u32>) {
+    save_static_strings(&CHECKPOINT_SYNC_URLS, checkpoint_sync_urls);
+    CHECKPOINT_QUORUM.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(quorum))
+            .expect("failed to save checkpoint quorum")
+    });
+}
+
+/// Fetches the latest finalized checkpoint from the consensus nodes and the configured
+/// checkpoint sync providers, requiring the configured quorum of them to agree.
+pub(crate) async fn fetch_configured_checkpoint(consensus_rpc_urls: &[String]) -> Result<String> {
+    let mut sources = consensus_rpc_urls.to_vec();
+    for url in load_static_strings(&CHECKPOINT_SYNC_URLS) {
+        if !sources.contains(&url) {
+            sources.push(url);
+        }
+    }
+    let quorum = CHECKPOINT_QUORUM.with(|cell| cell.borrow().get().0);
+
+    fetch_finalized_checkpoint(&sources, quorum).await
+}
+
+/// Fetches the latest finalized checkpoint from all the sources and returns it
+/// if at least `quorum` of them agree on it.
+///
+/// Sources are beacon nodes or checkpoint sync providers serving
+/// `/eth/v1/beacon/headers/finalized`. The quorum defaults to the majority of the sources.
+async fn fetch_finalized_checkpoint(sources: &[String], quorum: Option<u32>) -> Result<String> {
+    if sources.is_empty() {
+        bail!("No checkpoint sources");
+    }
+
+    let quorum = quorum.map_or(sources.len() / 2 + 1, |quorum| quorum as usize);
+    if quorum == 0 || quorum > sources.len() {
+        bail!(
+            "Checkpoint quorum must be between 1 and the number of sources ({}), got {quorum}",
+            sources.len()
+        );
+    }
+
+    let mut responses = Vec::with_capacity(sources.len());
+    for source in sources {
+        let response = fetch_finalized_root(source).await;
+        if let Err(e) = &response {
+            warn!("Fetching checkpoint from {source} failed: {e:?}");
+        }
+        responses.push((source, response));
+    }
+
+    // the roots are compared parsed, as the sources may differ in the case of the hex digits
+    let mut votes: HashMap<H256, usize> = HashMap::new();
+    for (_, response) in &responses {
+        if let Ok(root) = response {
+            *votes.entry(*root).or_default() += 1;
+        }
+    }
+
+    let agreed = votes
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .filter(|(_, count)| *count >= quorum)
+        .map(|(root, _)| format!("{root:?}"));
+
+    if let Some(checkpoint) = agreed {
+        return Ok(checkpoint);
+    }
+
+    let report = responses
+        .iter()
+        .map(|(source, response)| match response {
+            Ok(root) => format!("{source}: {root:?}"),
+            Err(e) => format!("{source}: error: {e}"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    bail!(
+        "Less than {quorum} of {} checkpoint sources agree: [{report}]",
+        sources.len()
+    )
+}
+
+async fn fetch_finalized_root(source: &str) -> Result<H256> {
+    let checkpoint_url = format!("{source}/eth/v1/beacon/headers/finalized");
+    let header_resp = http::get(&checkpoint_url)
+        .await
+        .wrap_err("Finalized header request failed")?;
+    let body = str::from_utf8(&header_resp.body).wrap_err("Non utf-8 response")?;
+    let header: Value = serde_json::from_str(body).wrap_err("Reading json response failed")?;
+    let checkpoint = header
+        .pointer("/data/root")
+        .and_then(Value::as_str)
+        .ok_or_else(|| eyre!("No root found in response: {body}"))?;
+    checkpoint
+        .parse()
+        .wrap_err_with(|| format!("Invalid root in response: {checkpoint}"))
+}
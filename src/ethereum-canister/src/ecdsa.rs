@@ -0,0 +1,102 @@
+use candid::Principal;
+use ethers_core::k256::ecdsa::{RecoveryId, Signature as K256Signature, VerifyingKey};
+use ethers_core::types::transaction::eip712::{Eip712, TypedData};
+use ethers_core::types::{Signature, U256};
+use eyre::{eyre, Result, WrapErr};
+use ic_cdk::api::management_canister::ecdsa::{
+    ecdsa_public_key, sign_with_ecdsa, EcdsaCurve, EcdsaKeyId, EcdsaPublicKeyArgument,
+    SignWithEcdsaArgument,
+};
+
+use crate::errors::InvalidInput;
+
+pub(crate) const DEFAULT_ECDSA_KEY_NAME: &str = "key_1";
+
+/// Prefixes the derivation path with the caller so that each caller gets its own set of keys.
+pub(crate) fn caller_derivation_path(
+    caller: Principal,
+    derivation_path: Vec<Vec<u8>>,
+) -> Vec<Vec<u8>> {
+    let mut path = Vec::with_capacity(derivation_path.len() + 1);
+    path.push(caller.as_slice().to_vec());
+    path.extend(derivation_path);
+    path
+}
+
+pub(crate) async fn sign_typed_data(
+    key_name: String,
+    derivation_path: Vec<Vec<u8>>,
+    typed_data_json: &str,
+) -> Result<Signature> {
+    let typed_data: TypedData = serde_json::from_str(typed_data_json)
+        .map_err(|e| InvalidInput(format!("Parsing typed data failed: {e}")))?;
+    let hash = typed_data
+        .encode_eip712()
+        .map_err(|e| InvalidInput(format!("Hashing typed data failed: {e}")))?;
+
+    sign_hash(key_name, derivation_path, hash).await
+}
+
+pub(crate) async fn sign_hash(
+    key_name: String,
+    derivation_path: Vec<Vec<u8>>,
+    hash: [u8; 32],
+) -> Result<Signature> {
+    let key_id = EcdsaKeyId {
+        curve: EcdsaCurve::Secp256k1,
+        name: key_name,
+    };
+
+    let (public_key,) = ecdsa_public_key(EcdsaPublicKeyArgument {
+        canister_id: None,
+        derivation_path: derivation_path.clone(),
+        key_id: key_id.clone(),
+    })
+    .await
+    .map_err(|(code, msg)| eyre!("ecdsa_public_key failed: {code:?} {msg}"))?;
+
+    let (signature,) = sign_with_ecdsa(SignWithEcdsaArgument {
+        message_hash: hash.to_vec(),
+        derivation_path,
+        key_id,
+    })
+    .await
+    .map_err(|(code, msg)| eyre!("sign_with_ecdsa failed: {code:?} {msg}"))?;
+
+    let public_key =
+        VerifyingKey::from_sec1_bytes(&public_key.public_key).wrap_err("Invalid public key")?;
+
+    to_recoverable_signature(&hash, &signature.signature, &public_key)
+}
+
+/// Converts the raw `r || s` signature returned by the management canister
+/// into an Ethereum signature by finding the matching recovery id.
+fn to_recoverable_signature(
+    hash: &[u8; 32],
+    signature: &[u8],
+    public_key: &VerifyingKey,
+) -> Result<Signature> {
+    let mut signature = K256Signature::from_slice(signature).wrap_err("Invalid signature")?;
+
+    // Ethereum only accepts signatures with `s` in the lower half of the curve order
+    if let Some(normalized) = signature.normalize_s() {
+        signature = normalized;
+    }
+
+    let recovery_id = [0, 1]
+        .into_iter()
+        .filter_map(RecoveryId::from_byte)
+        .find(|recovery_id| {
+            VerifyingKey::recover_from_prehash(hash, &signature, *recovery_id)
+                .map_or(false, |key| &key == public_key)
+        })
+        .ok_or_else(|| eyre!("Recovery id not found"))?;
+
+    let (r, s) = signature.split_bytes();
+
+    Ok(Signature {
+        r: U256::from_big_endian(&r),
+        s: U256::from_big_endian(&s),
+        v: 27 + u64::from(recovery_id.to_byte()),
+    })
+}
@@ -0,0 +1,144 @@
+use interface::RpcEndpointStats;
+
+/// How long an endpoint is skipped after it returned data with invalid proofs.
+const DEMOTION_NANOS: u64 = 10 * 60 * 1_000_000_000;
+
+/// A list of rpc endpoints tried in order, with the statistics of each of them.
+///
+/// Since helios verifies every response, any of the endpoints can be used
+/// and they are rotated when the current one fails.
+#[derive(Debug, Default)]
+pub(crate) struct Endpoints {
+    endpoints: Vec<Endpoint>,
+    current: usize,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    requests: u64,
+    errors: u64,
+    total_latency_nanos: u64,
+    demoted_until: Option<u64>,
+}
+
+impl Endpoint {
+    fn new(url: String) -> Self {
+        Endpoint {
+            url,
+            requests: 0,
+            errors: 0,
+            total_latency_nanos: 0,
+            demoted_until: None,
+        }
+    }
+
+    fn is_demoted(&self, now: u64) -> bool {
+        self.demoted_until.map_or(false, |until| until > now)
+    }
+}
+
+impl Endpoints {
+    /// Replaces the list of urls, keeping the statistics of the urls that were already known.
+    ///
+    /// The first endpoint that is not demoted becomes the current one.
+    pub(crate) fn set_urls(&mut self, urls: Vec<String>, now: u64) {
+        let mut old = std::mem::take(&mut self.endpoints);
+
+        self.endpoints = urls
+            .into_iter()
+            .map(
+                |url| match old.iter().position(|endpoint| endpoint.url == url) {
+                    Some(pos) => old.swap_remove(pos),
+                    None => Endpoint::new(url),
+                },
+            )
+            .collect();
+
+        self.current = self
+            .endpoints
+            .iter()
+            .position(|endpoint| !endpoint.is_demoted(now))
+            .unwrap_or_default();
+    }
+
+    pub(crate) fn urls(&self) -> Vec<String> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| endpoint.url.clone())
+            .collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub(crate) fn current(&self) -> Option<&str> {
+        self.endpoints
+            .get(self.current)
+            .map(|endpoint| endpoint.url.as_str())
+    }
+
+    fn get_mut(&mut self, url: &str) -> Option<&mut Endpoint> {
+        self.endpoints
+            .iter_mut()
+            .find(|endpoint| endpoint.url == url)
+    }
+
+    /// Records a request served by the endpoint, ignored if the url is no longer in the list.
+    pub(crate) fn record_success(&mut self, url: &str, latency_nanos: u64) {
+        if let Some(endpoint) = self.get_mut(url) {
+            endpoint.requests += 1;
+            endpoint.total_latency_nanos += latency_nanos;
+        }
+    }
+
+    /// Records a request the endpoint failed, ignored if the url is no longer in the list.
+    pub(crate) fn record_failure(&mut self, url: &str, latency_nanos: u64) {
+        if let Some(endpoint) = self.get_mut(url) {
+            endpoint.requests += 1;
+            endpoint.errors += 1;
+            endpoint.total_latency_nanos += latency_nanos;
+        }
+    }
+
+    /// Temporarily skips the endpoint when selecting the next one.
+    pub(crate) fn demote(&mut self, url: &str, now: u64) {
+        if let Some(endpoint) = self.get_mut(url) {
+            endpoint.demoted_until = Some(now + DEMOTION_NANOS);
+        }
+    }
+
+    /// Makes the next endpoint in order that is not demoted the current one.
+    ///
+    /// Returns `None` if there is no other endpoint to try.
+    pub(crate) fn select_next(&mut self, now: u64) -> Option<&str> {
+        let len = self.endpoints.len();
+
+        let next = (1..len)
+            .map(|offset| (self.current + offset) % len)
+            .find(|&idx| !self.endpoints[idx].is_demoted(now))?;
+
+        self.current = next;
+        self.current()
+    }
+
+    pub(crate) fn stats(&self, now: u64) -> Vec<RpcEndpointStats> {
+        self.endpoints
+            .iter()
+            .enumerate()
+            .map(|(idx, endpoint)| RpcEndpointStats {
+                url: endpoint.url.clone(),
+                current: idx == self.current,
+                requests: endpoint.requests,
+                errors: endpoint.errors,
+                average_latency_ms: endpoint
+                    .total_latency_nanos
+                    .checked_div(endpoint.requests)
+                    .unwrap_or_default()
+                    / 1_000_000,
+                demoted_until: endpoint.demoted_until.filter(|&until| until > now),
+            })
+            .collect()
+    }
+}
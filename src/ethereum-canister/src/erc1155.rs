@@ -0,0 +1,15 @@
+use contracts_abi::erc1155::*;
+use ethers_core::types::{Address, U256};
+use eyre::Result;
+
+use crate::helios;
+
+pub(crate) async fn balance_of(
+    erc1155_contract: Address,
+    account: Address,
+    id: U256,
+) -> Result<U256> {
+    let ret: BalanceOfReturn =
+        helios::call(erc1155_contract, BalanceOfCall { account, id }).await?;
+    Ok(ret.0)
+}
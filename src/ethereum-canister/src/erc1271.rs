@@ -0,0 +1,35 @@
+use contracts_abi::erc1271::*;
+use ethers_core::abi::{AbiDecode, AbiEncode};
+use ethers_core::types::{Address, H256};
+use eyre::Result;
+
+use crate::{errors, helios};
+
+/// Value returned by `isValidSignature` when the signature is valid.
+const MAGIC_VALUE: [u8; 4] = [0x16, 0x26, 0xba, 0x7e];
+
+/// Checks the signature of the hash with the contract's `isValidSignature`.
+///
+/// Accounts without code can't validate any signature. Contracts that revert or don't return
+/// the expected value, eg. because they don't implement ERC-1271, reject the signature.
+pub(crate) async fn is_valid_signature(
+    erc1271_contract: Address,
+    hash: H256,
+    signature: Vec<u8>,
+) -> Result<bool> {
+    if helios::get_code(erc1271_contract).await?.is_empty() {
+        return Ok(false);
+    }
+
+    let call = IsValidSignatureCall {
+        hash: hash.to_fixed_bytes(),
+        signature: signature.into(),
+    };
+    let bytes = match helios::call_raw(erc1271_contract, call.encode()).await {
+        Ok(bytes) => bytes,
+        Err(err) if errors::is_revert(&err) => return Ok(false),
+        Err(err) => return Err(err),
+    };
+
+    Ok(IsValidSignatureReturn::decode(bytes).map_or(false, |ret| ret.0 == MAGIC_VALUE))
+}
@@ -4,6 +4,11 @@ use eyre::Result;
 
 use crate::helios;
 
+pub(crate) async fn balance_of(erc721_contract: Address, owner: Address) -> Result<U256> {
+    let ret: BalanceOfReturn = helios::call(erc721_contract, BalanceOfCall { owner }).await?;
+    Ok(ret.0)
+}
+
 pub(crate) async fn owner_of(erc721_contract: Address, token_id: U256) -> Result<Address> {
     let ret: OwnerOfReturn = helios::call(erc721_contract, OwnerOfCall { token_id }).await?;
     Ok(ret.0)
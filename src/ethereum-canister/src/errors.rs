@@ -0,0 +1,235 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use ethers_core::types::SignatureError;
+use eyre::Report;
+use helios_client::errors::NodeError;
+use helios_common::errors::BlockNotFoundError;
+use helios_execution::errors::{EvmError, ExecutionError};
+use interface::EthError;
+
+use crate::allowlist::NotAllowed;
+use crate::payments::PaymentFailed;
+use crate::pricing::InsufficientCycles;
+use crate::quotas::QuotaExceeded;
+use crate::revert::Revert;
+use crate::staleness::StaleHead;
+
+/// Returned when the client is used before the canister is set up.
+#[derive(Debug)]
+pub(crate) struct NotInitialized;
+
+impl fmt::Display for NotInitialized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Client not started")
+    }
+}
+
+impl std::error::Error for NotInitialized {}
+
+/// Returned when the request can't be processed regardless of the state of the chain.
+#[derive(Debug)]
+pub(crate) struct InvalidInput(pub(crate) String);
+
+impl fmt::Display for InvalidInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidInput {}
+
+pub(crate) trait IntoEthResult<T> {
+    fn into_eth_result(self) -> Result<T, EthError>;
+}
+
+impl<T> IntoEthResult<T> for eyre::Result<T> {
+    fn into_eth_result(self) -> Result<T, EthError> {
+        self.map_err(|err| to_eth_error(&err))
+    }
+}
+
+/// Classifies the error, so the callers can decide whether to retry.
+pub(crate) fn to_eth_error(err: &Report) -> EthError {
+    let message = format!("{err:#}");
+    let causes = causes(err);
+    let find = |is: fn(&(dyn StdError + 'static)) -> bool| causes.iter().any(|cause| is(*cause));
+
+    if find(|cause| cause.is::<NotInitialized>()) {
+        return EthError::NotInitialized;
+    }
+
+    if let Some(stale) = causes
+        .iter()
+        .find_map(|cause| cause.downcast_ref::<StaleHead>())
+    {
+        return EthError::Stale {
+            head_age_secs: stale.head_age_secs,
+            max_head_age_secs: stale.max_head_age_secs,
+        };
+    }
+
+    if let Some(revert) = causes
+        .iter()
+        .find_map(|cause| cause.downcast_ref::<Revert>())
+    {
+        return EthError::ExecutionReverted {
+            data: revert.data.clone(),
+            reason: revert.reason.clone(),
+        };
+    }
+
+    if let Some(data) = revert_data(err) {
+        return EthError::ExecutionReverted { data, reason: None };
+    }
+
+    if is_invalid_proof(err) {
+        return EthError::ProofVerificationFailed { message };
+    }
+
+    if find(|cause| cause.is::<InvalidInput>() || cause.is::<SignatureError>()) {
+        return EthError::InvalidInput { message };
+    }
+
+    if let Some(payment) = causes
+        .iter()
+        .find_map(|cause| cause.downcast_ref::<PaymentFailed>())
+    {
+        return EthError::PaymentFailed {
+            message: payment.0.clone(),
+        };
+    }
+
+    if let Some(not_allowed) = causes
+        .iter()
+        .find_map(|cause| cause.downcast_ref::<NotAllowed>())
+    {
+        return EthError::NotAllowed {
+            message: not_allowed.0.clone(),
+        };
+    }
+
+    if let Some(quota) = causes
+        .iter()
+        .find_map(|cause| cause.downcast_ref::<QuotaExceeded>())
+    {
+        return EthError::QuotaExceeded {
+            retry_after_secs: quota.retry_after_secs,
+        };
+    }
+
+    if find(|cause| cause.is::<InsufficientCycles>()) {
+        return EthError::OutOfCycles;
+    }
+
+    if err.downcast_ref::<EndpointUnavailable>().is_some() {
+        return EthError::RpcUnavailable { message };
+    }
+
+    EthError::Other { message }
+}
+
+/// Added by [`crate::helios::execute`] to the errors of the requests the execution endpoint failed to serve
+/// or that couldn't be sent while the client is restarting.
+#[derive(Debug)]
+pub(crate) struct EndpointUnavailable;
+
+impl fmt::Display for EndpointUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Execution endpoint request failed")
+    }
+}
+
+/// How the execution endpoint handled a failed request.
+pub(crate) enum ExecutionFailure {
+    /// The endpoint served the request, the execution itself failed, e.g. the call reverted.
+    Execution,
+    /// The endpoint returned data that didn't match the verified state root.
+    InvalidProof,
+    /// The endpoint couldn't be reached or returned an error.
+    Unavailable,
+}
+
+pub(crate) fn execution_failure(err: &Report) -> ExecutionFailure {
+    if is_invalid_proof(err) {
+        return ExecutionFailure::InvalidProof;
+    }
+
+    let served = causes(err).into_iter().any(|cause| {
+        let evm_err = match cause.downcast_ref::<NodeError>() {
+            Some(NodeError::ExecutionEvmError(evm_err)) => Some(evm_err),
+            _ => cause.downcast_ref::<EvmError>(),
+        };
+        let evm_failed = evm_err.map_or(false, |evm_err| !matches!(evm_err, EvmError::RpcError(_)));
+
+        evm_failed || cause.is::<BlockNotFoundError>()
+    });
+
+    if served {
+        ExecutionFailure::Execution
+    } else {
+        ExecutionFailure::Unavailable
+    }
+}
+
+/// Returns the revert data if the error is a revert of the executed call.
+pub(crate) fn revert_data(err: &Report) -> Option<Vec<u8>> {
+    causes(err).into_iter().find_map(|cause| {
+        let evm_err = match cause.downcast_ref::<NodeError>() {
+            Some(NodeError::ExecutionEvmError(evm_err)) => evm_err,
+            _ => cause.downcast_ref::<EvmError>()?,
+        };
+
+        match evm_err {
+            EvmError::Revert(data) => {
+                Some(data.as_ref().map(|data| data.to_vec()).unwrap_or_default())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Checks if the error is caused by the called contract reverting.
+pub(crate) fn is_revert(err: &Report) -> bool {
+    causes(err).into_iter().any(|cause| cause.is::<Revert>()) || revert_data(err).is_some()
+}
+
+/// Checks if the error is caused by data that didn't match the verified state root.
+pub(crate) fn is_invalid_proof(err: &Report) -> bool {
+    causes(err).into_iter().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ExecutionError>(),
+            Some(
+                ExecutionError::InvalidAccountProof(..)
+                    | ExecutionError::InvalidStorageProof(..)
+                    | ExecutionError::CodeHashMismatch(..)
+            )
+        )
+    })
+}
+
+/// All the errors in the chain, including the ones helios keeps in reports
+/// that are not exposed as their sources.
+fn causes(err: &Report) -> Vec<&(dyn StdError + 'static)> {
+    let mut causes = Vec::new();
+    collect_causes(err, &mut causes);
+    causes
+}
+
+fn collect_causes<'a>(err: &'a Report, causes: &mut Vec<&'a (dyn StdError + 'static)>) {
+    for cause in err.chain() {
+        causes.push(cause);
+
+        let inner = match cause.downcast_ref::<NodeError>() {
+            Some(NodeError::ExecutionError(report)) => Some(report),
+            Some(NodeError::ExecutionEvmError(EvmError::RpcError(report))) => Some(report),
+            _ => match cause.downcast_ref::<EvmError>() {
+                Some(EvmError::RpcError(report)) => Some(report),
+                _ => None,
+            },
+        };
+        if let Some(report) = inner {
+            collect_causes(report, causes);
+        }
+    }
+}
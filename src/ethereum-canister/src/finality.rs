@@ -0,0 +1,90 @@
+use std::cell::RefCell;
+
+use ethers_core::types::{H256, U256};
+use eyre::{eyre, Result};
+use helios_common::types::BlockTag;
+
+use crate::errors::InvalidInput;
+use crate::helios;
+use crate::stable_memory::{init_stable_cell_default, Candid, StableCell, FINALIZED_ONLY_ID};
+
+/// Priority fee added to the base fee, same as the one used by helios for the latest block.
+const PRIORITY_FEE_WEI: u64 = 1_000_000_000;
+
+thread_local! {
+    static FINALIZED_ONLY: RefCell<StableCell<Candid<bool>>> = RefCell::new(init_stable_cell_default(FINALIZED_ONLY_ID));
+}
+
+pub(crate) fn finalized_only() -> bool {
+    FINALIZED_ONLY.with(|cell| cell.borrow().get().0)
+}
+
+pub(crate) fn set_finalized_only(finalized_only: bool) {
+    FINALIZED_ONLY.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(finalized_only))
+            .expect("failed to save finalized only mode")
+    });
+}
+
+/// The block that reads are served from.
+pub(crate) fn head_tag() -> BlockTag {
+    if finalized_only() {
+        BlockTag::Finalized
+    } else {
+        BlockTag::Latest
+    }
+}
+
+/// Hash of the block that reads are served from.
+pub(crate) async fn get_head_hash() -> Result<H256> {
+    let block = helios::client()?
+        .get_block_by_number(head_tag(), false)
+        .await?
+        .ok_or_else(|| eyre!("Head block not found"))?;
+
+    Ok(block.hash)
+}
+
+pub(crate) async fn get_block_number() -> Result<u64> {
+    let client = helios::client()?;
+
+    if !finalized_only() {
+        return client.get_block_number();
+    }
+
+    let block = client
+        .get_block_by_number(BlockTag::Finalized, false)
+        .await?
+        .ok_or_else(|| eyre!("Finalized block not found"))?;
+
+    Ok(block.number)
+}
+
+pub(crate) async fn get_gas_price() -> Result<U256> {
+    let client = helios::client()?;
+
+    if !finalized_only() {
+        return client.get_gas_price();
+    }
+
+    let block = client
+        .get_block_by_number(BlockTag::Finalized, false)
+        .await?
+        .ok_or_else(|| eyre!("Finalized block not found"))?;
+
+    Ok(block.base_fee_per_gas + PRIORITY_FEE_WEI)
+}
+
+/// Fails if the canister serves finalized data only, as gas estimation needs the latest block.
+pub(crate) fn ensure_latest_allowed() -> Result<()> {
+    if finalized_only() {
+        return Err(InvalidInput(
+            "Not available in the finalized only mode, gas is estimated on the latest block"
+                .to_owned(),
+        )
+        .into());
+    }
+
+    Ok(())
+}
@@ -1,6 +1,8 @@
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
 use std::rc::Rc;
-use std::str;
+use std::thread::LocalKey;
+use std::time::Duration;
 
 use ethers_contract::EthCall;
 use ethers_core::abi::{AbiDecode, AbiEncode};
@@ -8,53 +10,125 @@ use ethers_core::types::Address;
 use eyre::{bail, eyre, Result, WrapErr};
 use helios_client::database::ConfigDB;
 use helios_client::{Client, ClientBuilder};
-use helios_common::http;
-use helios_common::types::BlockTag;
-use helios_config::Network as HeliosNetwork;
 use helios_execution::types::CallOpts;
-use interface::Network;
-use serde_json::Value;
+use interface::{Network, RpcEndpointStats};
+use log::{error, warn};
+
+use crate::endpoints::Endpoints;
+use crate::errors::{EndpointUnavailable, ExecutionFailure, NotInitialized};
+use crate::revert::Revert;
+use crate::{call_cache, checkpoint, errors, finality, networks, revert, staleness, sync_monitor};
 
 thread_local! {
     static HELIOS: RefCell<Option<Rc<Client<ConfigDB>>>> = RefCell::new(None);
+    static CONFIG: RefCell<Option<ClientConfig>> = RefCell::new(None);
+    // execution endpoint the running client was started with
+    static EXECUTION_URL: RefCell<Option<String>> = RefCell::new(None);
+    static CONSENSUS_ENDPOINTS: RefCell<Endpoints> = RefCell::new(Endpoints::default());
+    static EXECUTION_ENDPOINTS: RefCell<Endpoints> = RefCell::new(Endpoints::default());
+    // start time of the restart in progress
+    static RESTARTING: Cell<Option<u64>> = Cell::new(None);
+    // an execution failover is scheduled but didn't start yet
+    static RESTART_SCHEDULED: Cell<bool> = Cell::new(false);
+}
+
+/// A restart running for longer is considered abandoned, e.g. after a trap, and another one may begin.
+const RESTART_TIMEOUT_NANOS: u64 = 10 * 60 * 1_000_000_000;
+
+/// Configuration of the running client, needed to restart it with another endpoint.
+#[derive(Debug, Clone)]
+struct ClientConfig {
+    network: Network,
+    /// Last checkpoint reached by the client, kept so that it can be restarted when it's down.
+    checkpoint: String,
 }
 
 pub(crate) fn try_client() -> Option<Rc<Client<ConfigDB>>> {
     HELIOS.with(|helios| helios.borrow().clone())
 }
 
-pub(crate) fn client() -> Rc<Client<ConfigDB>> {
-    try_client().expect("Client not started")
+pub(crate) fn client() -> Result<Rc<Client<ConfigDB>>> {
+    match try_client() {
+        Some(client) => Ok(client),
+        None if is_restarting() => {
+            Err(eyre!("Client is restarting with another endpoint").wrap_err(EndpointUnavailable))
+        }
+        None => Err(NotInitialized.into()),
+    }
 }
 
 pub(crate) async fn start_client(
     network: Network,
-    consensus_rpc_url: &str,
-    execution_rpc_url: &str,
+    consensus_rpc_urls: Vec<String>,
+    execution_rpc_urls: Vec<String>,
     checkpoint: Option<&str>,
 ) -> Result<()> {
     if HELIOS.with(|helios| helios.borrow().is_some()) {
         bail!("Client already started");
     }
 
-    let network = match network {
-        Network::Mainnet => HeliosNetwork::MAINNET,
-        Network::Goerli => HeliosNetwork::GOERLI,
-    };
+    let now = ic_cdk::api::time();
+    CONSENSUS_ENDPOINTS.with(|endpoints| endpoints.borrow_mut().set_urls(consensus_rpc_urls, now));
+    EXECUTION_ENDPOINTS.with(|endpoints| endpoints.borrow_mut().set_urls(execution_rpc_urls, now));
 
     let checkpoint = if let Some(checkpoint) = checkpoint {
         checkpoint.to_owned()
     } else {
-        fetch_latest_checkpoint(consensus_rpc_url)
+        let consensus_rpc_urls = CONSENSUS_ENDPOINTS.with(|endpoints| endpoints.borrow().urls());
+        checkpoint::fetch_configured_checkpoint(&consensus_rpc_urls)
             .await
             .wrap_err("Fetching latest checkpoint failed")?
     };
 
-    let mut client: Client<ConfigDB> = ClientBuilder::new()
-        .network(network)
-        .consensus_rpc(consensus_rpc_url)
-        .execution_rpc(execution_rpc_url)
-        .checkpoint(&checkpoint)
+    start_with_failover(ClientConfig {
+        network,
+        checkpoint,
+    })
+    .await
+}
+
+/// Starts the client, trying each of the consensus endpoints until one succeeds.
+async fn start_with_failover(config: ClientConfig) -> Result<()> {
+    let attempts = CONSENSUS_ENDPOINTS.with(|endpoints| endpoints.borrow().len().max(1));
+    let mut attempt = 1;
+
+    loop {
+        let err = match start(config.clone()).await {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+        let next_url = CONSENSUS_ENDPOINTS.with(|endpoints| {
+            endpoints
+                .borrow_mut()
+                .select_next(ic_cdk::api::time())
+                .map(ToOwned::to_owned)
+        });
+
+        let Some(next_url) = next_url.filter(|_| attempt < attempts) else {
+            return Err(err);
+        };
+
+        warn!("Starting client failed: {err:?}. Failing over to {next_url}");
+        attempt += 1;
+    }
+}
+
+/// Starts the client with the current consensus and execution endpoints.
+async fn start(config: ClientConfig) -> Result<()> {
+    if HELIOS.with(|helios| helios.borrow().is_some()) {
+        bail!("Client already started");
+    }
+
+    let consensus_rpc_url =
+        current_url(&CONSENSUS_ENDPOINTS).ok_or_else(|| eyre!("No consensus rpc url"))?;
+    let execution_rpc_url =
+        current_url(&EXECUTION_ENDPOINTS).ok_or_else(|| eyre!("No execution rpc url"))?;
+
+    let mut client: Client<ConfigDB> = networks::configure(ClientBuilder::new(), &config.network)?
+        .consensus_rpc(&consensus_rpc_url)
+        .execution_rpc(&execution_rpc_url)
+        .checkpoint(&config.checkpoint)
         .load_external_fallback()
         .build()
         .wrap_err("Client setup failed")?;
@@ -64,24 +138,46 @@ pub(crate) async fn start_client(
         .await
         .wrap_err("Failed to start the client")?;
 
+    if HELIOS.with(|helios| helios.borrow().is_some()) {
+        // started by someone else in the meantime
+        client.shutdown().await;
+        bail!("Client already started");
+    }
+
     HELIOS.with(|helios| *helios.borrow_mut() = Some(Rc::new(client)));
+    EXECUTION_URL.with(|url| *url.borrow_mut() = Some(execution_rpc_url));
+
+    sync_monitor::start(config.network.clone());
+
+    CONFIG.with(|cfg| *cfg.borrow_mut() = Some(config));
 
     Ok(())
 }
 
+fn current_url(endpoints: &'static LocalKey<RefCell<Endpoints>>) -> Option<String> {
+    endpoints.with(|endpoints| endpoints.borrow().current().map(ToOwned::to_owned))
+}
+
+/// Last checkpoint reached by the client, or the one it will be restarted from when it's down.
 pub(crate) fn get_last_checkpoint() -> Option<String> {
     match try_client() {
         Some(client) => client.get_last_checkpoint(),
-        None => None,
+        None => CONFIG.with(|cfg| cfg.borrow().as_ref().map(|cfg| cfg.checkpoint.clone())),
     }
 }
 
+/// Stops the client and the sync monitor, it's not restarted until started again.
 pub(crate) async fn shutdown() {
-    if let Some(client) = try_client() {
+    sync_monitor::stop();
+    stop_client().await;
+    CONFIG.with(|cfg| cfg.borrow_mut().take());
+}
+
+async fn stop_client() {
+    EXECUTION_URL.with(|url| url.borrow_mut().take());
+    if let Some(client) = HELIOS.with(|helios| helios.borrow_mut().take()) {
         client.shutdown().await;
     }
-
-    HELIOS.with(|helios| helios.borrow_mut().take());
 }
 
 pub(crate) async fn call<T, R>(contract: Address, call_data: T) -> Result<R>
@@ -89,31 +185,240 @@ where
     T: EthCall + AbiEncode,
     R: AbiDecode,
 {
+    let bytes = call_raw(contract, call_data.encode()).await?;
+    let ret = R::decode(bytes)?;
+
+    Ok(ret)
+}
+
+/// Calls the contract with the already encoded call data and returns the raw output.
+///
+/// The result is reused by the same calls until the head changes. It's only cached
+/// if the head didn't change while the call was executed, so it belongs to that block.
+pub(crate) async fn call_raw(contract: Address, call_data: Vec<u8>) -> Result<Vec<u8>> {
+    staleness::ensure_fresh().await?;
+
+    let head = finality::get_head_hash().await?;
+    if let Some(bytes) = call_cache::get(head, contract, &call_data) {
+        return Ok(bytes);
+    }
+
+    let bytes = execute_call(contract, call_data.clone()).await?;
+    if finality::get_head_hash().await? == head {
+        call_cache::insert(head, contract, call_data, bytes.clone());
+    }
+
+    Ok(bytes)
+}
+
+/// Same as [`call_raw`], but the call is always executed and its result is not cached.
+///
+/// Used for the dry runs, which have to make all the outcalls of the call.
+pub(crate) async fn call_raw_uncached(contract: Address, call_data: Vec<u8>) -> Result<Vec<u8>> {
+    staleness::ensure_fresh().await?;
+    execute_call(contract, call_data).await
+}
+
+async fn execute_call(contract: Address, call_data: Vec<u8>) -> Result<Vec<u8>> {
     let opts = CallOpts {
         from: None,
         to: Some(contract),
         gas: None,
         gas_price: None,
         value: None,
-        data: Some(call_data.encode()),
+        data: Some(call_data),
     };
 
-    let bytes = client().call(&opts, BlockTag::Latest).await?;
-    let ret = R::decode(bytes)?;
+    let opts = &opts;
+    execute(|client| async move { client.call(opts, finality::head_tag()).await })
+        .await
+        .map_err(|err| match errors::revert_data(&err) {
+            Some(data) => {
+                let reason = revert::decode(contract, &data);
+                Revert { data, reason }.into()
+            }
+            None => err,
+        })
+}
 
-    Ok(ret)
+/// Verified code of the account at the current head, empty for externally owned accounts.
+pub(crate) async fn get_code(address: Address) -> Result<Vec<u8>> {
+    let address = &address;
+    execute(|client| async move { client.get_code(address, finality::head_tag()).await }).await
+}
+
+/// Executes the request using the current execution endpoint and records its outcome.
+///
+/// If the endpoint failed to serve the request or returned invalid proofs, the request fails
+/// and the client is restarted with the next execution endpoint in the background, so the
+/// caller doesn't wait or pay for the failover. Endpoints that returned invalid proofs
+/// are temporarily demoted.
+pub(crate) async fn execute<F, Fut, T>(request: F) -> Result<T>
+where
+    F: FnOnce(Rc<Client<ConfigDB>>) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let client = client()?;
+    // the client is replaced by a restart, so its endpoint is taken along with it
+    let url = EXECUTION_URL
+        .with(|url| url.borrow().clone())
+        .unwrap_or_default();
+
+    let started_at = ic_cdk::api::time();
+    let result = request(client).await;
+    let latency = ic_cdk::api::time().saturating_sub(started_at);
+
+    let err = match result {
+        Ok(ret) => {
+            EXECUTION_ENDPOINTS
+                .with(|endpoints| endpoints.borrow_mut().record_success(&url, latency));
+            return Ok(ret);
+        }
+        Err(err) => err,
+    };
+
+    let failure = errors::execution_failure(&err);
+    if matches!(failure, ExecutionFailure::Execution) {
+        // endpoint responded correctly, the execution itself failed
+        EXECUTION_ENDPOINTS.with(|endpoints| endpoints.borrow_mut().record_success(&url, latency));
+        return Err(err);
+    }
+
+    EXECUTION_ENDPOINTS.with(|endpoints| {
+        let mut endpoints = endpoints.borrow_mut();
+        endpoints.record_failure(&url, latency);
+        if matches!(failure, ExecutionFailure::InvalidProof) {
+            endpoints.demote(&url, ic_cdk::api::time());
+        }
+    });
+
+    warn!("Execution request to {url} failed: {err:?}");
+    schedule_restart(url);
+
+    Err(err.wrap_err(EndpointUnavailable))
 }
 
-async fn fetch_latest_checkpoint(consensus_rpc_url: &str) -> Result<String> {
-    let checkpoint_url = format!("{consensus_rpc_url}/eth/v1/beacon/headers/finalized");
-    let header_resp = http::get(&checkpoint_url)
+/// Restarts the client with the execution endpoint after the failed one in a separate message,
+/// so the cycles are not attributed to the current caller.
+///
+/// Nothing is scheduled while another restart is pending or running, the failures of the
+/// concurrent requests come from the same endpoint.
+fn schedule_restart(failed_url: String) {
+    if is_restarting() || RESTART_SCHEDULED.with(Cell::get) {
+        return;
+    }
+    RESTART_SCHEDULED.with(|scheduled| scheduled.set(true));
+
+    ic_cdk_timers::set_timer(Duration::ZERO, || {
+        RESTART_SCHEDULED.with(|scheduled| scheduled.set(false));
+        ic_cdk::spawn(async move {
+            if let Err(err) = fail_over_execution(&failed_url).await {
+                error!("Execution failover failed: {err:?}");
+            }
+        })
+    });
+}
+
+/// Restarts the client with the execution endpoint after the failed one.
+///
+/// Nothing is done if the client doesn't use the failed endpoint anymore.
+async fn fail_over_execution(failed_url: &str) -> Result<()> {
+    restart(|| {
+        let running_url = EXECUTION_URL.with(|url| url.borrow().clone());
+        if running_url.as_deref() != Some(failed_url) {
+            return Ok(false);
+        }
+
+        EXECUTION_ENDPOINTS.with(|endpoints| {
+            let mut endpoints = endpoints.borrow_mut();
+            if endpoints.current() != Some(failed_url) {
+                // already moved on, eg. the urls were reconfigured
+                return Ok(true);
+            }
+            match endpoints.select_next(ic_cdk::api::time()) {
+                Some(next_url) => {
+                    warn!("Failing over from {failed_url} to {next_url}");
+                    Ok(true)
+                }
+                None => Err(eyre!("No other execution rpc url to fail over to")),
+            }
+        })
+    })
+    .await
+}
+
+/// Restarts the client with the next consensus endpoint.
+///
+/// Called when the light client stopped receiving updates from the current one
+/// or when it's down after a failed restart, in which case the current endpoint
+/// is retried if there is no other one.
+pub(crate) async fn fail_over_consensus() -> Result<()> {
+    restart(|| {
+        let next_url = CONSENSUS_ENDPOINTS.with(|endpoints| {
+            endpoints
+                .borrow_mut()
+                .select_next(ic_cdk::api::time())
+                .map(ToOwned::to_owned)
+        });
+
+        match next_url {
+            Some(next_url) => warn!("Consensus updates failed. Failing over to {next_url}"),
+            None if try_client().is_none() => warn!("Client is down. Starting it again"),
+            None => bail!("No other consensus rpc url to fail over to"),
+        }
+        Ok(true)
+    })
+    .await
+}
+
+/// Restarts the client from the last trusted checkpoint.
+///
+/// Only one restart runs at a time, the others fail until it's done. The endpoints are
+/// selected by `fail_over` once the restart begins, it returns `false` if no restart is needed.
+async fn restart(fail_over: impl FnOnce() -> Result<bool>) -> Result<()> {
+    if is_restarting() {
+        bail!("Client is already restarting");
+    }
+    RESTARTING.with(|restarting| restarting.set(Some(ic_cdk::api::time())));
+
+    let result = match fail_over() {
+        Ok(true) => restart_client().await,
+        Ok(false) => Ok(()),
+        Err(err) => Err(err),
+    };
+    RESTARTING.with(|restarting| restarting.set(None));
+
+    result
+}
+
+pub(crate) fn is_restarting() -> bool {
+    let now = ic_cdk::api::time();
+    RESTARTING.with(|restarting| {
+        restarting.get().map_or(false, |started_at| {
+            now.saturating_sub(started_at) < RESTART_TIMEOUT_NANOS
+        })
+    })
+}
+
+async fn restart_client() -> Result<()> {
+    let config = CONFIG
+        .with(|cfg| cfg.borrow().clone())
+        .ok_or(NotInitialized)?;
+    let checkpoint = get_last_checkpoint().ok_or_else(|| eyre!("No checkpoint to restart from"))?;
+    let config = ClientConfig {
+        checkpoint,
+        ..config
+    };
+    CONFIG.with(|cfg| *cfg.borrow_mut() = Some(config.clone()));
+
+    // the sync monitor keeps running, so the client is started again if this fails
+    stop_client().await;
+
+    start_with_failover(config)
         .await
-        .wrap_err("Finalized header request failed")?;
-    let body = str::from_utf8(&header_resp.body).wrap_err("Non utf-8 response")?;
-    let header: Value = serde_json::from_str(body).wrap_err("Reading json response failed")?;
-    let checkpoint = header
-        .pointer("/data/root")
-        .and_then(Value::as_str)
-        .ok_or_else(|| eyre!("No root found in response: {body}"))?;
-    Ok(checkpoint.to_owned())
+        .wrap_err("Restarting client failed")
+}
+
+pub(crate) fn execution_rpc_stats() -> Vec<RpcEndpointStats> {
+    EXECUTION_ENDPOINTS.with(|endpoints| endpoints.borrow().stats(ic_cdk::api::time()))
 }
@@ -0,0 +1,131 @@
+//! Subset of the ICRC-1 and ICRC-2 ledger interface needed to collect and withdraw payments.
+
+use candid::{CandidType, Nat, Principal};
+use eyre::{eyre, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub(crate) struct Account {
+    pub(crate) owner: Principal,
+    pub(crate) subaccount: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub(crate) struct TransferFromArgs {
+    pub(crate) spender_subaccount: Option<Vec<u8>>,
+    pub(crate) from: Account,
+    pub(crate) to: Account,
+    pub(crate) amount: Nat,
+    pub(crate) fee: Option<Nat>,
+    pub(crate) memo: Option<Vec<u8>>,
+    pub(crate) created_at_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub(crate) enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub(crate) struct TransferArgs {
+    pub(crate) from_subaccount: Option<Vec<u8>>,
+    pub(crate) to: Account,
+    pub(crate) amount: Nat,
+    pub(crate) fee: Option<Nat>,
+    pub(crate) memo: Option<Vec<u8>>,
+    pub(crate) created_at_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, CandidType, Deserialize)]
+pub(crate) enum TransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+/// Transfers the amount from the canister's default account.
+///
+/// Returns the index of the transfer block.
+pub(crate) async fn transfer(ledger: Principal, to: Principal, amount: u128) -> Result<Nat> {
+    let args = TransferArgs {
+        from_subaccount: None,
+        to: Account {
+            owner: to,
+            subaccount: None,
+        },
+        amount: amount.into(),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+
+    let (result,): (Result<Nat, TransferError>,) = ic_cdk::call(ledger, "icrc1_transfer", (args,))
+        .await
+        .map_err(|(code, msg)| eyre!("icrc1_transfer failed: {code:?} {msg}"))?;
+
+    result.map_err(|e| eyre!("icrc1_transfer failed: {e:?}"))
+}
+
+/// Transfers the amount approved by the owner to the canister's default account.
+///
+/// Returns the index of the transfer block.
+pub(crate) async fn transfer_from(ledger: Principal, from: Principal, amount: u128) -> Result<Nat> {
+    let args = TransferFromArgs {
+        spender_subaccount: None,
+        from: Account {
+            owner: from,
+            subaccount: None,
+        },
+        to: Account {
+            owner: ic_cdk::id(),
+            subaccount: None,
+        },
+        amount: amount.into(),
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+
+    let (result,): (Result<Nat, TransferFromError>,) =
+        ic_cdk::call(ledger, "icrc2_transfer_from", (args,))
+            .await
+            .map_err(|(code, msg)| eyre!("icrc2_transfer_from failed: {code:?} {msg}"))?;
+
+    result.map_err(|e| eyre!("icrc2_transfer_from failed: {e:?}"))
+}
+
+/// Returns the balance of the owner's default account.
+pub(crate) async fn balance_of(ledger: Principal, owner: Principal) -> Result<u128> {
+    let account = Account {
+        owner,
+        subaccount: None,
+    };
+
+    let (balance,): (Nat,) = ic_cdk::call(ledger, "icrc1_balance_of", (account,))
+        .await
+        .map_err(|(code, msg)| eyre!("icrc1_balance_of failed: {code:?} {msg}"))?;
+
+    u128::try_from(balance.0).map_err(|_| eyre!("icrc1_balance_of returned too large balance"))
+}
+
+/// Returns the fee the ledger charges for a transfer.
+pub(crate) async fn fee(ledger: Principal) -> Result<u128> {
+    let (fee,): (Nat,) = ic_cdk::call(ledger, "icrc1_fee", ())
+        .await
+        .map_err(|(code, msg)| eyre!("icrc1_fee failed: {code:?} {msg}"))?;
+
+    u128::try_from(fee.0).map_err(|_| eyre!("icrc1_fee returned too large fee"))
+}
@@ -1,32 +1,71 @@
 use std::cell::RefCell;
+use std::thread::LocalKey;
 
-use candid::Nat;
+use candid::{CandidType, Nat, Principal};
+use ic_cdk::api::call::ManualReply;
 use ic_cdk::{init, post_upgrade, pre_upgrade, query, update};
 use ic_cdk_timers::set_timer;
 use interface::{
-    Address, Erc20BalanceOfRequest, Erc721OwnerOfRequest, EstimateGasRequest, Network,
-    SetupRequest, U256,
+    Address, AssignQuotaTierRequest, CallCost, CheckTokenGateRequest,
+    Erc1271IsValidSignatureRequest, Erc20BalanceOfRequest, Erc721OwnerOfRequest,
+    EstimateCallCostRequest, EstimateGasRequest, EthError, LedgerConfig, Network, PriceList, Quota,
+    QuotaTier, ReconfigureRequest, RecoverAddressRequest, RegisterAbiRequest, RpcEndpointStats,
+    SetupRequest, SignTypedDataRequest, Signature, SiweLoginRequest, SyncStatus,
+    VerifyPersonalMessageRequest, WithdrawTokensRequest, U256,
 };
 use log::{debug, error};
 
+use crate::access_control::caller_is_admin;
+use crate::errors::IntoEthResult;
 use crate::stable_memory::{
-    init_stable_cell_default, load_static_string, save_static_string, StableCell,
-    LAST_CHECKPOINT_ID, LAST_CONSENSUS_RPC_URL_ID, LAST_EXECUTION_RPC_URL_ID, LAST_NETWORK_ID,
+    init_stable_cell_default, load_static_string, load_static_strings, save_static_string,
+    save_static_strings, Candid, StableCell, CONSENSUS_RPC_URLS_ID, ECDSA_KEY_NAME_ID,
+    EXECUTION_RPC_URLS_ID, LAST_CHECKPOINT_ID, LAST_CONSENSUS_RPC_URL_ID,
+    LAST_EXECUTION_RPC_URL_ID, LAST_NETWORK_ID, NETWORK_ID, SIWE_DOMAIN_ID,
 };
-use crate::utils::IntoCallOpts;
 
+mod access_control;
+mod allowlist;
+mod api;
+mod call_cache;
+mod checkpoint;
+mod ecdsa;
+mod endpoints;
+mod erc1155;
+mod erc1271;
 mod erc20;
 mod erc721;
+mod errors;
+mod finality;
 mod helios;
+mod icrc2;
+mod metering;
+mod networks;
+mod payments;
+mod pricing;
+mod quotas;
 mod random;
+mod revert;
+mod signature;
+mod siwe;
 mod stable_memory;
+mod staleness;
+mod sync_monitor;
+mod token_gate;
 mod utils;
 
 thread_local! {
+    // Only read when upgrading from a version that supported the named networks only
     static LAST_NETWORK: RefCell<StableCell<String>> = RefCell::new(init_stable_cell_default(LAST_NETWORK_ID));
+    static NETWORK: RefCell<StableCell<Candid<Option<Network>>>> = RefCell::new(init_stable_cell_default(NETWORK_ID));
+    // Only read when upgrading from a version that supported a single url per node type
     static LAST_CONSENSUS_RPC_URL: RefCell<StableCell<String>> = RefCell::new(init_stable_cell_default(LAST_CONSENSUS_RPC_URL_ID));
     static LAST_EXECUTION_RPC_URL: RefCell<StableCell<String>> = RefCell::new(init_stable_cell_default(LAST_EXECUTION_RPC_URL_ID));
+    static CONSENSUS_RPC_URLS: RefCell<StableCell<Candid<Vec<String>>>> = RefCell::new(init_stable_cell_default(CONSENSUS_RPC_URLS_ID));
+    static EXECUTION_RPC_URLS: RefCell<StableCell<Candid<Vec<String>>>> = RefCell::new(init_stable_cell_default(EXECUTION_RPC_URLS_ID));
     static LAST_CHECKPOINT: RefCell<StableCell<String>> = RefCell::new(init_stable_cell_default(LAST_CHECKPOINT_ID));
+    static ECDSA_KEY_NAME: RefCell<StableCell<String>> = RefCell::new(init_stable_cell_default(ECDSA_KEY_NAME_ID));
+    static SIWE_DOMAIN: RefCell<StableCell<String>> = RefCell::new(init_stable_cell_default(SIWE_DOMAIN_ID));
 }
 
 #[init]
@@ -36,77 +75,448 @@ async fn init() {
 
 /// Setup the helios client with given node urls
 ///
+/// Without a checkpoint, the latest finalized one is fetched from the consensus nodes
+/// and the checkpoint sync providers and the quorum of them must agree on it.
+///
+/// Can only be called by the controllers or the admins.
+///
 /// Mainnet:
 ///   dfx canister call ethereum_canister setup \
-///     'record { network = variant { Mainnet }; execution_rpc_url = "https://ethereum.publicnode.com"; consensus_rpc_url = "https://www.lightclientdata.org" }'
+///     'record { network = variant { Mainnet }; execution_rpc_urls = vec { "https://ethereum.publicnode.com"; "https://eth.llamarpc.com" }; consensus_rpc_urls = vec { "https://www.lightclientdata.org" } }'
 ///
 /// Goerli:
 ///   dfx canister call ethereum_canister setup \
-///     'record { network = variant { Goerli }; execution_rpc_url = "https://ethereum-goerli.publicnode.com"; consensus_rpc_url = "TODO" }'
-#[update]
+///     'record { network = variant { Goerli }; execution_rpc_urls = vec { "https://ethereum-goerli.publicnode.com" }; consensus_rpc_urls = vec { "TODO" } }'
+///
+/// Sepolia:
+///   dfx canister call ethereum_canister setup \
+///     'record { network = variant { Sepolia }; execution_rpc_urls = vec { "https://ethereum-sepolia.publicnode.com" }; consensus_rpc_urls = vec { "https://ethereum-sepolia-beacon-api.publicnode.com" } }'
+///
+/// Holesky:
+///   dfx canister call ethereum_canister setup \
+///     'record { network = variant { Holesky }; execution_rpc_urls = vec { "https://ethereum-holesky.publicnode.com" }; consensus_rpc_urls = vec { "https://ethereum-holesky-beacon-api.publicnode.com" } }'
+#[update(guard = "caller_is_admin")]
 async fn setup(request: SetupRequest) {
     let _ = ic_logger::init_with_level(log::Level::Trace);
 
+    // saved first, as the checkpoint may be fetched again on reconfiguration or after an upgrade
+    checkpoint::set_sync_config(
+        request.checkpoint_sync_urls.unwrap_or_default(),
+        request.checkpoint_quorum,
+    );
+
+    let checkpoint = match request.checkpoint {
+        Some(checkpoint) => checkpoint,
+        None => checkpoint::fetch_configured_checkpoint(&request.consensus_rpc_urls)
+            .await
+            .expect("fetching checkpoint failed"),
+    };
+
     helios::start_client(
-        request.network,
-        &request.consensus_rpc_url,
-        &request.execution_rpc_url,
-        request.checkpoint.as_deref(),
+        request.network.clone(),
+        request.consensus_rpc_urls.clone(),
+        request.execution_rpc_urls.clone(),
+        Some(&checkpoint),
     )
     .await
     .expect("starting client failed");
 
-    save_static_string(&LAST_NETWORK, request.network.to_string());
-    save_static_string(&LAST_CONSENSUS_RPC_URL, request.consensus_rpc_url);
-    save_static_string(&LAST_EXECUTION_RPC_URL, request.execution_rpc_url);
+    save_network(request.network);
+    save_static_strings(&CONSENSUS_RPC_URLS, request.consensus_rpc_urls);
+    save_static_strings(&EXECUTION_RPC_URLS, request.execution_rpc_urls);
+    save_static_string(&ECDSA_KEY_NAME, request.ecdsa_key_name);
+    save_static_string(&SIWE_DOMAIN, request.siwe_domain);
+    staleness::set_max_head_age(request.max_head_age_secs);
+    finality::set_finalized_only(request.finalized_only.unwrap_or_default());
+}
+
+/// Restart the helios client with new node urls, keeping the last trusted checkpoint
+///
+/// Urls that are not provided stay the same. If the client fails to start with
+/// the new urls, it is restarted with the previous ones.
+///
+///   dfx canister call ethereum_canister reconfigure \
+///     'record { execution_rpc_urls = opt vec { "https://eth.llamarpc.com" } }'
+#[update(guard = "caller_is_admin")]
+async fn reconfigure(request: ReconfigureRequest) {
+    let network = load_network().expect("Canister is not set up");
+    let old_consensus_rpc_urls = load_static_strings(&CONSENSUS_RPC_URLS);
+    let old_execution_rpc_urls = load_static_strings(&EXECUTION_RPC_URLS);
+
+    let consensus_rpc_urls = request
+        .consensus_rpc_urls
+        .unwrap_or_else(|| old_consensus_rpc_urls.clone());
+    let execution_rpc_urls = request
+        .execution_rpc_urls
+        .unwrap_or_else(|| old_execution_rpc_urls.clone());
+
+    let checkpoint = helios::get_last_checkpoint().or_else(|| load_static_string(&LAST_CHECKPOINT));
+    save_static_string(&LAST_CHECKPOINT, checkpoint.clone());
+
+    debug!(
+        "Reconfiguring client with: execution_rpc_urls = {:?}, consensus_rpc_urls = {:?}, checkpoint: {}",
+        &execution_rpc_urls,
+        &consensus_rpc_urls,
+        &checkpoint.as_deref().unwrap_or("None"),
+    );
+
+    helios::shutdown().await;
+
+    if let Err(e) = helios::start_client(
+        network.clone(),
+        consensus_rpc_urls.clone(),
+        execution_rpc_urls.clone(),
+        checkpoint.as_deref(),
+    )
+    .await
+    {
+        error!("Starting client with new urls failed: {e:?}. Restoring previous urls.");
+
+        helios::start_client(
+            network,
+            old_consensus_rpc_urls,
+            old_execution_rpc_urls,
+            checkpoint.as_deref(),
+        )
+        .await
+        .expect("restarting client failed");
+
+        panic!("starting client failed: {e:?}");
+    }
+
+    save_static_strings(&CONSENSUS_RPC_URLS, consensus_rpc_urls);
+    save_static_strings(&EXECUTION_RPC_URLS, execution_rpc_urls);
+}
+
+/// Set the maximum age of the latest verified header in seconds
+///
+/// Reads fail with a stale error when the head is older. `None` disables the check.
+#[update(guard = "caller_is_admin")]
+fn set_max_head_age(max_head_age_secs: Option<u64>) {
+    staleness::set_max_head_age(max_head_age_secs);
+}
+
+/// Allow the principal to call the admin methods
+///
+/// Controllers of the canister are always admins.
+#[update(guard = "caller_is_admin")]
+fn add_admin(principal: Principal) {
+    access_control::add_admin(principal);
+}
+
+#[update(guard = "caller_is_admin")]
+fn remove_admin(principal: Principal) {
+    access_control::remove_admin(principal);
+}
+
+/// Register the ABI used to decode the custom errors of the contract's reverted calls
+#[update(guard = "caller_is_admin")]
+fn register_abi(request: RegisterAbiRequest) {
+    revert::register_abi(request.contract.into(), request.abi_json).expect("register_abi failed");
+}
+
+#[update(guard = "caller_is_admin")]
+fn unregister_abi(contract: Address) {
+    revert::unregister_abi(contract.into());
+}
+
+/// Set the cycles charged for the update calls, `None` makes them free
+#[update(guard = "caller_is_admin")]
+fn set_price_list(price_list: Option<PriceList>) {
+    pricing::set_price_list(price_list).expect("set_price_list failed");
+}
+
+/// Cycles charged for the update calls, `None` if they are free
+#[query]
+fn get_price_list() -> Option<PriceList> {
+    pricing::price_list()
+}
+
+/// Set the ICRC-2 ledger used to pay for the calls without cycles attached, `None` disables it
+#[update(guard = "caller_is_admin")]
+fn set_ledger(ledger: Option<LedgerConfig>) {
+    payments::set_ledger(ledger).expect("set_ledger failed");
+}
+
+#[query]
+fn get_ledger() -> Option<LedgerConfig> {
+    payments::ledger()
+}
+
+/// Tokens paid by the principal and not spent yet
+#[query]
+fn get_prepaid_balance(principal: Principal) -> Nat {
+    payments::prepaid_balance(principal).into()
+}
+
+/// Transfer the collected tokens out of the canister's account, returns the ledger block index
+#[update(guard = "caller_is_admin")]
+async fn withdraw_tokens(request: WithdrawTokensRequest) -> Nat {
+    payments::withdraw(request.to, request.amount)
+        .await
+        .expect("withdraw_tokens failed")
+}
+
+/// Set the principals allowed to use the canister, `None` allows everyone
+#[update(guard = "caller_is_admin")]
+fn set_caller_allowlist(principals: Option<Vec<Principal>>) {
+    allowlist::set_caller_allowlist(principals);
+}
+
+#[query]
+fn get_caller_allowlist() -> Option<Vec<Principal>> {
+    allowlist::caller_allowlist()
+}
+
+/// Set the contracts that can be called, `None` allows all of them
+#[update(guard = "caller_is_admin")]
+fn set_contract_allowlist(contracts: Option<Vec<Address>>) {
+    allowlist::set_contract_allowlist(contracts);
+}
+
+#[query]
+fn get_contract_allowlist() -> Option<Vec<Address>> {
+    allowlist::contract_allowlist()
+}
+
+/// Replace the quota tiers, an empty list removes the limits
+#[update(guard = "caller_is_admin")]
+fn set_quota_tiers(tiers: Vec<QuotaTier>) {
+    quotas::set_tiers(tiers).expect("set_quota_tiers failed");
+}
+
+#[query]
+fn get_quota_tiers() -> Vec<QuotaTier> {
+    quotas::tiers()
+}
+
+#[update(guard = "caller_is_admin")]
+fn assign_quota_tier(request: AssignQuotaTierRequest) {
+    quotas::assign_tier(request.principal, request.tier).expect("assign_quota_tier failed");
+}
+
+/// Limits of the principal and its current usage, `None` if it's not limited
+#[query]
+fn get_quota(principal: Principal) -> Option<Quota> {
+    quotas::quota(principal)
+}
+
+#[query]
+fn get_admins() -> Vec<Principal> {
+    access_control::admins()
+}
+
+#[query]
+async fn get_block_number() -> Nat {
+    api::get_block_number()
+        .await
+        .expect("get_block_number failed")
+        .into()
+}
+
+#[query]
+async fn get_block_number_result() -> Result<Nat, EthError> {
+    api::get_block_number()
+        .await
+        .map(Into::into)
+        .into_eth_result()
+}
+
+#[query]
+async fn get_gas_price() -> U256 {
+    api::get_gas_price()
+        .await
+        .expect("get_gas_price failed")
+        .into()
 }
 
 #[query]
-fn get_block_number() -> Nat {
-    let helios = helios::client();
+async fn get_gas_price_result() -> Result<U256, EthError> {
+    api::get_gas_price().await.map(Into::into).into_eth_result()
+}
+
+/// Health of the background synchronization of the helios client
+#[query]
+async fn get_sync_status() -> SyncStatus {
+    sync_monitor::status()
+        .await
+        .expect("sync_monitor::status failed")
+}
 
-    let head_block_num = helios.get_block_number().expect("get_block_number failed");
+#[query]
+async fn get_sync_status_result() -> Result<SyncStatus, EthError> {
+    sync_monitor::status().await.into_eth_result()
+}
 
-    head_block_num.into()
+/// Network the canister was set up with, `None` before the setup
+#[query]
+fn get_network() -> Option<Network> {
+    load_network()
 }
 
+/// Statistics of the configured execution endpoints
 #[query]
-fn get_gas_price() -> U256 {
-    let helios = helios::client();
+fn get_execution_rpc_stats() -> Vec<RpcEndpointStats> {
+    helios::execution_rpc_stats()
+}
 
-    let gas_price = helios.get_gas_price().expect("get_gas_price failed");
+#[update(manual_reply = true)]
+async fn estimate_gas(request: EstimateGasRequest) -> ManualReply<U256> {
+    let result = api::estimate_gas(request).await.map(U256::from);
+    reply_or_reject(result, "estimate_gas failed")
+}
 
-    gas_price.into()
+#[update]
+async fn estimate_gas_result(request: EstimateGasRequest) -> Result<U256, EthError> {
+    api::estimate_gas(request)
+        .await
+        .map(Into::into)
+        .into_eth_result()
+}
+
+/// Dry runs the call against the current head and returns its cost, the outcalls of the dry run are charged at cost
+#[update(manual_reply = true)]
+async fn estimate_call_cost(request: EstimateCallCostRequest) -> ManualReply<CallCost> {
+    let result = api::estimate_call_cost(request).await;
+    reply_or_reject(result, "estimate_call_cost failed")
 }
 
 #[update]
-async fn estimate_gas(request: EstimateGasRequest) -> U256 {
-    let helios = helios::client();
+async fn estimate_call_cost_result(request: EstimateCallCostRequest) -> Result<CallCost, EthError> {
+    api::estimate_call_cost(request).await.into_eth_result()
+}
 
-    let gas_cost_estimation = helios
-        .estimate_gas(&request.into_call_opts())
+#[update(manual_reply = true)]
+async fn erc20_balance_of(request: Erc20BalanceOfRequest) -> ManualReply<U256> {
+    let result = api::erc20_balance_of(request).await.map(U256::from);
+    reply_or_reject(result, "erc20::balance_of failed")
+}
+
+#[update]
+async fn erc20_balance_of_result(request: Erc20BalanceOfRequest) -> Result<U256, EthError> {
+    api::erc20_balance_of(request)
         .await
-        .expect("estimate_gas failed");
+        .map(Into::into)
+        .into_eth_result()
+}
 
-    gas_cost_estimation.into()
+#[update(manual_reply = true)]
+async fn erc721_owner_of(request: Erc721OwnerOfRequest) -> ManualReply<Address> {
+    let result = api::erc721_owner_of(request).await.map(Address::from);
+    reply_or_reject(result, "erc721::owner_of failed")
 }
 
 #[update]
-async fn erc20_balance_of(request: Erc20BalanceOfRequest) -> U256 {
-    erc20::balance_of(request.contract.into(), request.account.into())
+async fn erc721_owner_of_result(request: Erc721OwnerOfRequest) -> Result<Address, EthError> {
+    api::erc721_owner_of(request)
         .await
-        .expect("erc20::balance_of failed")
-        .into()
+        .map(Into::into)
+        .into_eth_result()
+}
+
+#[update(manual_reply = true)]
+async fn erc1271_is_valid_signature(request: Erc1271IsValidSignatureRequest) -> ManualReply<bool> {
+    let result = api::erc1271_is_valid_signature(request).await;
+    reply_or_reject(result, "erc1271::is_valid_signature failed")
 }
 
 #[update]
-async fn erc721_owner_of(request: Erc721OwnerOfRequest) -> Address {
-    erc721::owner_of(request.contract.into(), request.token_id.into())
+async fn erc1271_is_valid_signature_result(
+    request: Erc1271IsValidSignatureRequest,
+) -> Result<bool, EthError> {
+    api::erc1271_is_valid_signature(request)
         .await
-        .expect("erc721::owner_of failed")
+        .into_eth_result()
+}
+
+/// Sign EIP-712 typed data with the threshold ECDSA key of the caller
+///
+/// The derivation path is always prefixed with the caller's principal,
+/// so each caller can only sign with its own keys.
+#[update(manual_reply = true)]
+async fn sign_typed_data(request: SignTypedDataRequest) -> ManualReply<Signature> {
+    let result = api::sign_typed_data(request).await.map(Signature::from);
+    reply_or_reject(result, "ecdsa::sign_typed_data failed")
+}
+
+#[update]
+async fn sign_typed_data_result(request: SignTypedDataRequest) -> Result<Signature, EthError> {
+    api::sign_typed_data(request)
+        .await
+        .map(Into::into)
+        .into_eth_result()
+}
+
+#[query]
+fn recover_address(request: RecoverAddressRequest) -> Address {
+    api::recover_address(request)
+        .expect("signature::recover_address failed")
         .into()
 }
 
+#[query]
+fn recover_address_result(request: RecoverAddressRequest) -> Result<Address, EthError> {
+    api::recover_address(request)
+        .map(Into::into)
+        .into_eth_result()
+}
+
+#[query]
+fn verify_personal_message(request: VerifyPersonalMessageRequest) -> bool {
+    signature::verify_personal_message(
+        request.address.into(),
+        &request.message,
+        &request.signature.into(),
+    )
+}
+
+/// Create a Sign-In with Ethereum (EIP-4361) message for the caller to sign
+#[update]
+async fn siwe_prepare(address: Address) -> String {
+    api::siwe_prepare(address)
+        .await
+        .expect("siwe::prepare failed")
+}
+
+#[update]
+async fn siwe_prepare_result(address: Address) -> Result<String, EthError> {
+    api::siwe_prepare(address).await.into_eth_result()
+}
+
+/// Verify the signed message from `siwe_prepare` and link the caller to the address
+#[update(manual_reply = true)]
+async fn siwe_login(request: SiweLoginRequest) -> ManualReply<Address> {
+    let result = api::siwe_login(request).await.map(Address::from);
+    reply_or_reject(result, "siwe::login failed")
+}
+
+#[update]
+async fn siwe_login_result(request: SiweLoginRequest) -> Result<Address, EthError> {
+    api::siwe_login(request)
+        .await
+        .map(Into::into)
+        .into_eth_result()
+}
+
+#[query]
+fn get_linked_address(principal: Principal) -> Option<Address> {
+    siwe::linked_address(principal).map(Into::into)
+}
+
+/// Check if the Ethereum address linked to the principal satisfies the rule
+///
+/// Returns `false` if the principal has no linked address.
+#[update(manual_reply = true)]
+async fn check_token_gate(request: CheckTokenGateRequest) -> ManualReply<bool> {
+    let result = api::check_token_gate(request).await;
+    reply_or_reject(result, "token_gate::evaluate failed")
+}
+
+#[update]
+async fn check_token_gate_result(request: CheckTokenGateRequest) -> Result<bool, EthError> {
+    api::check_token_gate(request).await.into_eth_result()
+}
+
 #[pre_upgrade]
 async fn pre_upgrade() {
     debug!("Stopping client");
@@ -127,37 +537,34 @@ fn post_upgrade() {
     // Client will be started from a timer in a second.
     set_timer(std::time::Duration::from_secs(1), || {
         ic_cdk::spawn(async move {
-            let Some(network) = load_static_string(&LAST_NETWORK) else {
+            let Some(network) = load_network() else {
                 return;
             };
 
-            let Ok(network) = network.parse::<Network>() else {
-                error!("Failed to parse network: {network}. Use `setup` to initalize canister.");
+            let consensus_rpc_urls = load_rpc_urls(&CONSENSUS_RPC_URLS, &LAST_CONSENSUS_RPC_URL);
+            if consensus_rpc_urls.is_empty() {
                 return;
-            };
+            }
 
-            let Some(consensus_rpc_url) = load_static_string(&LAST_CONSENSUS_RPC_URL) else {
+            let execution_rpc_urls = load_rpc_urls(&EXECUTION_RPC_URLS, &LAST_EXECUTION_RPC_URL);
+            if execution_rpc_urls.is_empty() {
                 return;
-            };
-
-            let Some(execution_rpc_url) = load_static_string(&LAST_EXECUTION_RPC_URL) else {
-                return;
-            };
+            }
 
             let checkpoint = load_static_string(&LAST_CHECKPOINT);
 
             debug!(
-                "Resuming client with: network = {}, execution_rpc_url = {}, consensus_rpc_url = {}, checkpoint: {}",
+                "Resuming client with: network = {}, execution_rpc_urls = {:?}, consensus_rpc_urls = {:?}, checkpoint: {}",
                 network,
-                &execution_rpc_url,
-                &consensus_rpc_url,
+                &execution_rpc_urls,
+                &consensus_rpc_urls,
                 &checkpoint.as_deref().unwrap_or("None"),
             );
 
             helios::start_client(
                 network,
-                &consensus_rpc_url,
-                &execution_rpc_url,
+                consensus_rpc_urls,
+                execution_rpc_urls,
                 checkpoint.as_deref(),
             )
             .await
@@ -165,3 +572,53 @@ fn post_upgrade() {
         });
     });
 }
+
+/// Replies with the value or rejects the call with the error.
+///
+/// Used instead of panicking by the endpoints that may be charged, as a trap
+/// would revert accepting the cycles of the failed call.
+fn reply_or_reject<T: CandidType>(result: eyre::Result<T>, context: &str) -> ManualReply<T> {
+    match result {
+        Ok(value) => ManualReply::one(value),
+        Err(err) => ManualReply::reject(format!("{context}: {err:?}")),
+    }
+}
+
+/// Loads the list of urls, migrating the single url stored by the previous versions
+fn load_rpc_urls(
+    urls: &'static LocalKey<RefCell<StableCell<Candid<Vec<String>>>>>,
+    legacy_url: &'static LocalKey<RefCell<StableCell<String>>>,
+) -> Vec<String> {
+    let loaded = load_static_strings(urls);
+    if !loaded.is_empty() {
+        return loaded;
+    }
+
+    let migrated: Vec<String> = load_static_string(legacy_url).into_iter().collect();
+    save_static_strings(urls, migrated.clone());
+    migrated
+}
+
+fn save_network(network: Network) {
+    NETWORK.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(Some(network)))
+            .expect("failed to save network")
+    });
+}
+
+/// Loads the network, migrating the name stored by the previous versions
+fn load_network() -> Option<Network> {
+    if let Some(network) = NETWORK.with(|cell| cell.borrow().get().0.clone()) {
+        return Some(network);
+    }
+
+    let legacy = load_static_string(&LAST_NETWORK)?;
+    let Ok(network) = legacy.parse::<Network>() else {
+        error!("Failed to parse network: {legacy}. Use `setup` to initalize canister.");
+        return None;
+    };
+
+    save_network(network.clone());
+    Some(network)
+}
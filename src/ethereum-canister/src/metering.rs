@@ -0,0 +1,81 @@
+//! Accounting of the calls made by a single request.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use eyre::Result;
+use ic_cdk::api::canister_balance128;
+
+/// Calls made by a request to other canisters, and the cycles attached to them.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Usage {
+    /// Calls the request waited for. These are the https outcalls, except for the
+    /// threshold ECDSA signing which is counted as one too.
+    pub(crate) outcalls: u32,
+    pub(crate) cycles: u128,
+}
+
+/// Result of a request with the calls it made.
+pub(crate) struct Metered<T> {
+    pub(crate) result: Result<T>,
+    pub(crate) usage: Usage,
+}
+
+impl<T> Metered<T> {
+    pub(crate) fn failed(err: eyre::Report) -> Self {
+        Self {
+            result: Err(err),
+            usage: Usage::default(),
+        }
+    }
+}
+
+/// Executes the request and counts the calls it makes.
+///
+/// The canister executes one message at a time, and the request is only polled in the message
+/// of the endpoint and in the callbacks of its own calls. So every poll but the first one follows
+/// a response to one of its calls, and the balance lost during a poll was attached to its calls.
+/// The calls of the concurrent requests and of the background sync are not counted in.
+pub(crate) async fn meter<F, T>(request: F) -> Metered<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    Meter {
+        request: Box::pin(request),
+        usage: Usage::default(),
+        polled: false,
+    }
+    .await
+}
+
+struct Meter<F> {
+    request: Pin<Box<F>>,
+    usage: Usage,
+    polled: bool,
+}
+
+impl<F, T> Future for Meter<F>
+where
+    F: Future<Output = Result<T>>,
+{
+    type Output = Metered<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.polled {
+            this.usage.outcalls = this.usage.outcalls.saturating_add(1);
+        }
+        this.polled = true;
+
+        let balance_before = canister_balance128();
+        let poll = this.request.as_mut().poll(cx);
+        let spent = balance_before.saturating_sub(canister_balance128());
+        this.usage.cycles = this.usage.cycles.saturating_add(spent);
+
+        poll.map(|result| Metered {
+            result,
+            usage: this.usage,
+        })
+    }
+}
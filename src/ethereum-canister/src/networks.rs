@@ -0,0 +1,111 @@
+use ethers_core::types::H256 as EthersH256;
+use ethers_core::utils::hex;
+use eyre::{ensure, Result, WrapErr};
+use helios_client::ClientBuilder;
+use helios_config::{ChainConfig, Config, Fork, Forks, Network as HeliosNetwork};
+use interface::{CustomNetwork, Network};
+
+/// Configures the network of the client.
+///
+/// Networks that helios doesn't know about are given as a full chain and forks configuration.
+pub(crate) fn configure(builder: ClientBuilder, network: &Network) -> Result<ClientBuilder> {
+    let builder = match network {
+        Network::Mainnet => builder.network(HeliosNetwork::MAINNET),
+        Network::Goerli => builder.network(HeliosNetwork::GOERLI),
+        Network::Sepolia => builder.config(sepolia()),
+        Network::Holesky => builder.config(holesky()),
+        Network::Custom(custom) => builder.config(custom_config(custom)?),
+    };
+
+    Ok(builder)
+}
+
+/// Beacon chain genesis time in seconds since the unix epoch.
+pub(crate) fn genesis_time(network: &Network) -> u64 {
+    match network {
+        Network::Mainnet => 1_606_824_023,
+        Network::Goerli => 1_616_508_000,
+        Network::Sepolia => 1_655_733_600,
+        Network::Holesky => 1_695_902_400,
+        Network::Custom(custom) => custom.genesis_time,
+    }
+}
+
+fn sepolia() -> Config {
+    Config {
+        chain: ChainConfig {
+            chain_id: Network::Sepolia.chain_id(),
+            genesis_time: genesis_time(&Network::Sepolia),
+            genesis_root: decode(
+                "d8ea171f3c94aea21ebc42a1ed61052acf3f9209c00e4efbaaddac09ed9b8078",
+            ),
+        },
+        forks: Forks {
+            genesis: fork(0, "90000069"),
+            altair: fork(50, "90000070"),
+            bellatrix: fork(100, "90000071"),
+            capella: fork(56_832, "90000072"),
+        },
+        ..Default::default()
+    }
+}
+
+fn holesky() -> Config {
+    Config {
+        chain: ChainConfig {
+            chain_id: Network::Holesky.chain_id(),
+            genesis_time: genesis_time(&Network::Holesky),
+            genesis_root: decode(
+                "9143aa7c615a7f7115e2b6aac319c03529df8242ae705fba9df39b79c59fa8b1",
+            ),
+        },
+        forks: Forks {
+            genesis: fork(0, "01017000"),
+            altair: fork(0, "02017000"),
+            bellatrix: fork(0, "03017000"),
+            capella: fork(256, "04017000"),
+        },
+        ..Default::default()
+    }
+}
+
+fn custom_config(custom: &CustomNetwork) -> Result<Config> {
+    Ok(Config {
+        chain: ChainConfig {
+            chain_id: custom.chain_id,
+            genesis_time: custom.genesis_time,
+            genesis_root: EthersH256::from(custom.genesis_validators_root)
+                .as_bytes()
+                .to_vec(),
+        },
+        forks: Forks {
+            genesis: custom_fork(&custom.genesis).wrap_err("Invalid genesis fork")?,
+            altair: custom_fork(&custom.altair).wrap_err("Invalid altair fork")?,
+            bellatrix: custom_fork(&custom.bellatrix).wrap_err("Invalid bellatrix fork")?,
+            capella: custom_fork(&custom.capella).wrap_err("Invalid capella fork")?,
+        },
+        ..Default::default()
+    })
+}
+
+fn custom_fork(fork: &interface::Fork) -> Result<Fork> {
+    let version = fork.version.strip_prefix("0x").unwrap_or(&fork.version);
+    let fork_version = hex::decode(version).wrap_err("Fork version is not hex")?;
+    ensure!(fork_version.len() == 4, "Fork version must be 4 bytes");
+
+    Ok(Fork {
+        epoch: fork.epoch,
+        fork_version,
+    })
+}
+
+fn fork(epoch: u64, fork_version: &str) -> Fork {
+    Fork {
+        epoch,
+        fork_version: decode(fork_version),
+    }
+}
+
+fn decode(s: &str) -> Vec<u8> {
+    hex::decode(s).expect("invalid hex constant")
+}
@@ -0,0 +1,179 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use candid::{Nat, Principal};
+use eyre::{eyre, Result};
+use interface::LedgerConfig;
+use log::debug;
+
+use crate::errors::InvalidInput;
+use crate::icrc2;
+use crate::stable_memory::{
+    init_stable_btree_map, init_stable_cell_default, Candid, StableBTreeMap, StableCell,
+    StorablePrincipal, StorableU128, LEDGER_ID, PREPAID_BALANCES_ID,
+};
+
+thread_local! {
+    static LEDGER: RefCell<StableCell<Candid<Option<LedgerConfig>>>> = RefCell::new(init_stable_cell_default(LEDGER_ID));
+    static PREPAID_BALANCES: RefCell<StableBTreeMap<StorablePrincipal, StorableU128>> =
+        RefCell::new(init_stable_btree_map(PREPAID_BALANCES_ID));
+    static PULLS_IN_PROGRESS: RefCell<BTreeSet<Principal>> = RefCell::new(BTreeSet::new());
+}
+
+/// Returned when the tokens couldn't be pulled from the caller's account.
+#[derive(Debug)]
+pub(crate) struct PaymentFailed(pub(crate) String);
+
+impl fmt::Display for PaymentFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Payment failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for PaymentFailed {}
+
+pub(crate) fn ledger() -> Option<LedgerConfig> {
+    LEDGER.with(|cell| cell.borrow().get().0.clone())
+}
+
+/// Sets the ledger used to pay for the calls without cycles attached, `None` disables it.
+pub(crate) fn set_ledger(ledger: Option<LedgerConfig>) -> Result<()> {
+    if let Some(ledger) = &ledger {
+        if ledger.tokens_per_trillion_cycles == 0 || ledger.transfer_amount == 0 {
+            return Err(InvalidInput(
+                "Token price and transfer amount must be positive".to_owned(),
+            )
+            .into());
+        }
+    }
+
+    LEDGER.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(ledger))
+            .expect("failed to save ledger")
+    });
+    Ok(())
+}
+
+pub(crate) fn prepaid_balance(principal: Principal) -> u128 {
+    PREPAID_BALANCES.with(|balances| {
+        balances
+            .borrow()
+            .get(&StorablePrincipal(principal))
+            .map_or(0, |balance| balance.0)
+    })
+}
+
+fn set_prepaid_balance(principal: Principal, balance: u128) {
+    PREPAID_BALANCES.with(|balances| {
+        let mut balances = balances.borrow_mut();
+        if balance == 0 {
+            balances.remove(&StorablePrincipal(principal));
+        } else {
+            balances.insert(StorablePrincipal(principal), StorableU128(balance));
+        }
+    });
+}
+
+/// Amount of tokens worth the cycles, rounded up.
+pub(crate) fn to_tokens(ledger: &LedgerConfig, cycles: u128) -> u128 {
+    const TRILLION: u128 = 1_000_000_000_000;
+
+    let tokens = cycles.saturating_mul(ledger.tokens_per_trillion_cycles);
+    tokens / TRILLION + u128::from(tokens % TRILLION != 0)
+}
+
+/// Makes sure the prepaid balance of the caller covers the required amount,
+/// pulling the approved tokens from the caller's account when it doesn't.
+///
+/// At least `transfer_amount` is pulled, the part not spent stays prepaid for the next calls.
+/// Only one pull per caller runs at a time, so concurrent calls can't pull the tokens twice.
+pub(crate) async fn ensure_prepaid(
+    ledger: &LedgerConfig,
+    caller: Principal,
+    required: u128,
+) -> Result<()> {
+    let balance = prepaid_balance(caller);
+    if balance >= required {
+        return Ok(());
+    }
+
+    let _pull = PullGuard::new(caller)?;
+
+    // read again, as another pull could have finished in the meantime
+    let balance = prepaid_balance(caller);
+    if balance >= required {
+        return Ok(());
+    }
+
+    let amount = ledger.transfer_amount.max(required - balance);
+    let block_index = icrc2::transfer_from(ledger.ledger, caller, amount)
+        .await
+        .map_err(|e| PaymentFailed(format!("{e:#}")))?;
+    debug!("Received {amount} tokens from {caller} in block {block_index}");
+
+    // read again, as the balance could have changed during the transfer
+    set_prepaid_balance(caller, prepaid_balance(caller).saturating_add(amount));
+
+    Ok(())
+}
+
+/// Charges the prepaid balance of the caller, pulling more tokens if it doesn't cover the amount.
+///
+/// If the tokens can't be pulled, the whole prepaid balance is charged and the call fails.
+pub(crate) async fn debit(ledger: &LedgerConfig, caller: Principal, amount: u128) -> Result<()> {
+    let prepaid = ensure_prepaid(ledger, caller, amount).await;
+
+    let balance = prepaid_balance(caller);
+    set_prepaid_balance(caller, balance.saturating_sub(amount));
+
+    prepaid
+}
+
+/// Transfers the collected tokens out of the canister's account.
+///
+/// The tokens still prepaid by the callers are kept.
+pub(crate) async fn withdraw(to: Principal, amount: u128) -> Result<Nat> {
+    let ledger = ledger().ok_or_else(|| eyre!("No ledger configured"))?;
+
+    let collected = icrc2::balance_of(ledger.ledger, ic_cdk::id()).await?;
+    let fee = icrc2::fee(ledger.ledger).await?;
+    // read after the calls, as the balances could have changed in the meantime
+    let prepaid = PREPAID_BALANCES.with(|balances| {
+        balances
+            .borrow()
+            .iter()
+            .fold(0_u128, |sum, (_, balance)| sum.saturating_add(balance.0))
+    });
+    let available = collected.saturating_sub(prepaid).saturating_sub(fee);
+    if amount > available {
+        return Err(InvalidInput(format!(
+            "Only {available} tokens can be withdrawn, {prepaid} are prepaid by the callers"
+        ))
+        .into());
+    }
+
+    icrc2::transfer(ledger.ledger, to, amount).await
+}
+
+/// Marks the pull of the caller's tokens in progress until it's dropped.
+struct PullGuard(Principal);
+
+impl PullGuard {
+    fn new(caller: Principal) -> Result<Self> {
+        let started = PULLS_IN_PROGRESS.with(|pulls| pulls.borrow_mut().insert(caller));
+        if !started {
+            return Err(
+                PaymentFailed("Another payment of the caller is in progress".to_owned()).into(),
+            );
+        }
+        Ok(Self(caller))
+    }
+}
+
+impl Drop for PullGuard {
+    fn drop(&mut self) {
+        PULLS_IN_PROGRESS.with(|pulls| pulls.borrow_mut().remove(&self.0));
+    }
+}
@@ -0,0 +1,160 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+
+use eyre::Result;
+use ic_cdk::api::call::{msg_cycles_accept128, msg_cycles_available128};
+use interface::PriceList;
+
+use crate::errors::InvalidInput;
+use crate::metering::{meter, Metered, Usage};
+use crate::payments;
+use crate::stable_memory::{init_stable_cell_default, Candid, StableCell, PRICE_LIST_ID};
+
+thread_local! {
+    // None means that the calls are free
+    static PRICE_LIST: RefCell<StableCell<Candid<Option<PriceList>>>> = RefCell::new(init_stable_cell_default(PRICE_LIST_ID));
+}
+
+/// Returned when the call doesn't have enough cycles attached.
+#[derive(Debug)]
+pub(crate) struct InsufficientCycles {
+    pub(crate) attached: u128,
+    pub(crate) required: u128,
+}
+
+impl fmt::Display for InsufficientCycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Insufficient cycles attached: {}, at least {} required",
+            self.attached, self.required
+        )
+    }
+}
+
+impl std::error::Error for InsufficientCycles {}
+
+pub(crate) fn price_list() -> Option<PriceList> {
+    PRICE_LIST.with(|cell| cell.borrow().get().0.clone())
+}
+
+/// Sets the prices, `None` makes the calls free.
+///
+/// The attached cycles have to cover at least the base fee and one outcall, so that
+/// the calls can't be served for free.
+pub(crate) fn set_price_list(price_list: Option<PriceList>) -> Result<()> {
+    if let Some(price_list) = &price_list {
+        if price_list.outcall_fee == 0 {
+            return Err(InvalidInput("Outcall fee must be positive".to_owned()).into());
+        }
+        let minimum = price(price_list, 1);
+        if price_list.min_attached_cycles < minimum {
+            return Err(InvalidInput(format!(
+                "Minimum attached cycles must cover the base fee and one outcall: {minimum}"
+            ))
+            .into());
+        }
+    }
+
+    PRICE_LIST.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(price_list))
+            .expect("failed to save price list")
+    });
+    Ok(())
+}
+
+/// Price of a call that made the given number of https outcalls.
+pub(crate) fn price(price_list: &PriceList, outcalls: u32) -> u128 {
+    price_list
+        .outcall_fee
+        .saturating_mul(outcalls.into())
+        .saturating_add(price_list.base_fee)
+}
+
+/// Executes the request and accepts the cycles it cost from the attached ones.
+///
+/// Callers that don't attach any cycles pay with the tokens of the configured ledger instead.
+///
+/// The price is the base fee plus the fee of each outcall counted by [`meter`], which is returned
+/// so that it can be counted in the caller's quota. The request is charged even if it fails,
+/// as the outcalls were made anyway. If it cost more than the attached cycles, only the base fee
+/// is accepted and the call fails.
+pub(crate) async fn charge<F, T>(request: F) -> Metered<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    charge_with(request, |price_list, usage| {
+        price(price_list, usage.outcalls)
+    })
+    .await
+}
+
+/// Same as [`charge`], but only the cycles spent on the outcalls are charged, without any fees.
+///
+/// Used for the dry runs, which are worth making before the calls that will be made many times.
+pub(crate) async fn charge_at_cost<F, T>(request: F) -> Metered<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    charge_with(request, |_, usage| usage.cycles).await
+}
+
+async fn charge_with<F, T>(request: F, price: impl FnOnce(&PriceList, &Usage) -> u128) -> Metered<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let Some(price_list) = price_list() else {
+        return meter(request).await;
+    };
+
+    let caller = ic_cdk::caller();
+    let attached = msg_cycles_available128();
+    // calls without cycles attached are paid from the ledger, if there is one
+    let ledger = payments::ledger().filter(|_| attached == 0);
+
+    match &ledger {
+        Some(ledger) => {
+            let required = payments::to_tokens(ledger, price_list.min_attached_cycles);
+            if let Err(err) = payments::ensure_prepaid(ledger, caller, required).await {
+                return Metered::failed(err);
+            }
+        }
+        None if attached < price_list.min_attached_cycles => {
+            return Metered::failed(
+                InsufficientCycles {
+                    attached,
+                    required: price_list.min_attached_cycles,
+                }
+                .into(),
+            );
+        }
+        None => {}
+    }
+
+    let mut metered = meter(request).await;
+    let price = price(&price_list, &metered.usage);
+
+    match &ledger {
+        Some(ledger) => {
+            let amount = payments::to_tokens(ledger, price);
+            if let Err(err) = payments::debit(ledger, caller, amount).await {
+                metered.result = Err(err);
+            }
+        }
+        None if price > attached => {
+            msg_cycles_accept128(price_list.base_fee);
+            metered.result = Err(InsufficientCycles {
+                attached,
+                required: price,
+            }
+            .into());
+        }
+        None => {
+            msg_cycles_accept128(price);
+        }
+    }
+
+    metered
+}
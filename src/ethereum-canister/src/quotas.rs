@@ -0,0 +1,229 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+
+use candid::Principal;
+use eyre::Result;
+use interface::{Quota, QuotaTier};
+
+use crate::errors::InvalidInput;
+use crate::metering::Metered;
+use crate::stable_memory::{
+    init_stable_btree_map, init_stable_cell_default, Candid, StableBTreeMap, StableCell,
+    StorableName, StorablePrincipal, QUOTA_ASSIGNMENTS_ID, QUOTA_TIERS_ID,
+};
+
+/// Tier applied to the principals without one assigned.
+pub(crate) const DEFAULT_TIER: &str = "default";
+
+const MINUTE_SECS: u64 = 60;
+const HOUR_SECS: u64 = 60 * 60;
+
+/// Usage older than the current hour is dropped once this many principals are tracked,
+/// new principals are rejected until the hour is over if it's still full.
+const MAX_TRACKED_PRINCIPALS: usize = 10_000;
+
+thread_local! {
+    static QUOTA_TIERS: RefCell<StableCell<Candid<Vec<QuotaTier>>>> = RefCell::new(init_stable_cell_default(QUOTA_TIERS_ID));
+    static QUOTA_ASSIGNMENTS: RefCell<StableBTreeMap<StorablePrincipal, StorableName>> =
+        RefCell::new(init_stable_btree_map(QUOTA_ASSIGNMENTS_ID));
+    // the usage is not persisted, the windows start over after an upgrade
+    static USAGE: RefCell<HashMap<Principal, Usage>> = RefCell::new(HashMap::new());
+}
+
+/// Returned when the caller has used up its quota.
+#[derive(Debug)]
+pub(crate) struct QuotaExceeded {
+    pub(crate) retry_after_secs: u64,
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Quota exceeded, retry after {} seconds",
+            self.retry_after_secs
+        )
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Usage of a principal in the current fixed windows.
+#[derive(Debug, Default, Clone, Copy)]
+struct Usage {
+    minute: u64,
+    requests: u32,
+    hour: u64,
+    outcalls: u32,
+}
+
+impl Usage {
+    /// Starts new windows if the stored ones are over.
+    fn current(mut self, now_secs: u64) -> Self {
+        let minute = now_secs / MINUTE_SECS;
+        if self.minute != minute {
+            self.minute = minute;
+            self.requests = 0;
+        }
+        let hour = now_secs / HOUR_SECS;
+        if self.hour != hour {
+            self.hour = hour;
+            self.outcalls = 0;
+        }
+        self
+    }
+}
+
+pub(crate) fn tiers() -> Vec<QuotaTier> {
+    QUOTA_TIERS.with(|cell| cell.borrow().get().0.clone())
+}
+
+/// Replaces all the tiers, an empty list disables the limits.
+pub(crate) fn set_tiers(tiers: Vec<QuotaTier>) -> Result<()> {
+    for (i, tier) in tiers.iter().enumerate() {
+        if tier.name.len() > StorableName::MAX_LENGTH {
+            return Err(InvalidInput(format!(
+                "Quota tier name longer than {} bytes: {}",
+                StorableName::MAX_LENGTH,
+                tier.name
+            ))
+            .into());
+        }
+        if tiers[..i].iter().any(|other| other.name == tier.name) {
+            return Err(InvalidInput(format!("Duplicate quota tier: {}", tier.name)).into());
+        }
+    }
+
+    QUOTA_TIERS.with(|cell| {
+        cell.borrow_mut()
+            .set(Candid(tiers))
+            .expect("failed to save quota tiers")
+    });
+    Ok(())
+}
+
+/// Assigns the tier to the principal, `None` moves it back to the default tier.
+pub(crate) fn assign_tier(principal: Principal, tier: Option<String>) -> Result<()> {
+    if let Some(tier) = &tier {
+        if !tiers().iter().any(|other| &other.name == tier) {
+            return Err(InvalidInput(format!("Unknown quota tier: {tier}")).into());
+        }
+    }
+
+    QUOTA_ASSIGNMENTS.with(|assignments| {
+        let mut assignments = assignments.borrow_mut();
+        match tier {
+            Some(tier) => assignments.insert(StorablePrincipal(principal), StorableName(tier)),
+            None => assignments.remove(&StorablePrincipal(principal)),
+        };
+    });
+    Ok(())
+}
+
+/// Tier of the principal, `None` if it's not limited.
+///
+/// Principals assigned to a tier that was removed fall back to the default one.
+fn tier_of(principal: Principal) -> Option<QuotaTier> {
+    let assigned = QUOTA_ASSIGNMENTS.with(|assignments| {
+        assignments
+            .borrow()
+            .get(&StorablePrincipal(principal))
+            .map(|name| name.0)
+    });
+    let tiers = tiers();
+
+    assigned
+        .and_then(|name| tiers.iter().find(|tier| tier.name == name))
+        .or_else(|| tiers.iter().find(|tier| tier.name == DEFAULT_TIER))
+        .cloned()
+}
+
+fn usage_of(principal: Principal, now_secs: u64) -> Usage {
+    USAGE
+        .with(|usage| usage.borrow().get(&principal).copied())
+        .unwrap_or_default()
+        .current(now_secs)
+}
+
+fn save_usage(principal: Principal, usage: Usage, now_secs: u64) -> Result<()> {
+    USAGE.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        if cell.len() >= MAX_TRACKED_PRINCIPALS && !cell.contains_key(&principal) {
+            cell.retain(|_, other| other.hour == usage.hour);
+            if cell.len() >= MAX_TRACKED_PRINCIPALS {
+                return Err(QuotaExceeded {
+                    retry_after_secs: HOUR_SECS - now_secs % HOUR_SECS,
+                }
+                .into());
+            }
+        }
+        cell.insert(principal, usage);
+        Ok(())
+    })
+}
+
+/// Quota of the principal and its usage, `None` if it's not limited.
+pub(crate) fn quota(principal: Principal) -> Option<Quota> {
+    let tier = tier_of(principal)?;
+    let usage = usage_of(principal, now_secs());
+
+    Some(Quota {
+        tier: tier.name,
+        requests_per_minute: tier.requests_per_minute,
+        outcalls_per_hour: tier.outcalls_per_hour,
+        requests_in_current_minute: usage.requests,
+        outcalls_in_current_hour: usage.outcalls,
+    })
+}
+
+/// Executes the request if the caller has some quota left and counts it in.
+///
+/// The request is rejected when the caller made too many requests this minute
+/// or used up its outcalls for this hour. A request is allowed as long as there
+/// is at least one outcall left, and its outcalls are counted by [`crate::metering::meter`]
+/// after it finishes.
+pub(crate) async fn limit<F, T>(request: F) -> Result<T>
+where
+    F: Future<Output = Metered<T>>,
+{
+    let caller = ic_cdk::caller();
+    let Some(tier) = tier_of(caller) else {
+        return request.await.result;
+    };
+
+    let now = now_secs();
+    let mut usage = usage_of(caller, now);
+    if usage.requests >= tier.requests_per_minute {
+        return Err(QuotaExceeded {
+            retry_after_secs: MINUTE_SECS - now % MINUTE_SECS,
+        }
+        .into());
+    }
+    if usage.outcalls >= tier.outcalls_per_hour {
+        return Err(QuotaExceeded {
+            retry_after_secs: HOUR_SECS - now % HOUR_SECS,
+        }
+        .into());
+    }
+    usage.requests += 1;
+    save_usage(caller, usage, now)?;
+
+    let Metered { result, usage } = request.await;
+    let outcalls = usage.outcalls;
+
+    // the windows could have moved on during the request
+    let now = now_secs();
+    let mut usage = usage_of(caller, now);
+    usage.outcalls = usage.outcalls.saturating_add(outcalls);
+    // the entry is only evicted when a new hour started, if the map filled up since then
+    // the outcalls are not counted, the result is returned anyway
+    let _ = save_usage(caller, usage, now);
+
+    result
+}
+
+fn now_secs() -> u64 {
+    ic_cdk::api::time() / 1_000_000_000
+}
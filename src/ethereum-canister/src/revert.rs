@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use contracts_abi::erc6093::ERC6093_ABI;
+use ethers_core::abi::{self, Abi, AbiError, ParamType};
+use ethers_core::types::{Address, U256};
+use ethers_core::utils::id;
+use eyre::{Result, WrapErr};
+use interface::RevertReason;
+
+use crate::errors::InvalidInput;
+use crate::stable_memory::{init_stable_cell_default, Candid, StableCell, REGISTERED_ABIS_ID};
+
+/// Selector of `Error(string)`.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+thread_local! {
+    static REGISTERED_ABIS: RefCell<StableCell<Candid<BTreeMap<interface::Address, String>>>> = RefCell::new(init_stable_cell_default(REGISTERED_ABIS_ID));
+}
+
+/// Returned when the executed call reverted.
+#[derive(Debug)]
+pub(crate) struct Revert {
+    pub(crate) data: Vec<u8>,
+    pub(crate) reason: Option<RevertReason>,
+}
+
+impl fmt::Display for Revert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Some(RevertReason::Error { message }) => write!(f, "Execution reverted: {message}"),
+            Some(RevertReason::Panic { code, description }) => {
+                write!(f, "Execution reverted: panic {code:?}: {description}")
+            }
+            Some(RevertReason::Custom { name, args }) => {
+                write!(f, "Execution reverted: {name}({})", args.join(", "))
+            }
+            None => write!(
+                f,
+                "Execution reverted: 0x{}",
+                ethers_core::utils::hex::encode(&self.data)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Revert {}
+
+/// Registers the ABI used to decode the custom errors of the contract.
+pub(crate) fn register_abi(contract: Address, abi_json: String) -> Result<()> {
+    serde_json::from_str::<Abi>(&abi_json)
+        .map_err(|e| InvalidInput(format!("Invalid ABI: {e}")))?;
+
+    REGISTERED_ABIS.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let mut abis = cell.get().0.clone();
+        abis.insert(contract.into(), abi_json);
+        cell.set(Candid(abis)).wrap_err("failed to save ABIs")
+    })?;
+
+    Ok(())
+}
+
+pub(crate) fn unregister_abi(contract: Address) {
+    REGISTERED_ABIS.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let mut abis = cell.get().0.clone();
+        abis.remove(&contract.into());
+        cell.set(Candid(abis)).expect("failed to save ABIs");
+    });
+}
+
+fn registered_abi(contract: Address) -> Option<Abi> {
+    let abi_json =
+        REGISTERED_ABIS.with(|cell| cell.borrow().get().0.get(&contract.into()).cloned())?;
+    serde_json::from_str(&abi_json).ok()
+}
+
+/// Decodes the revert data of a call to the contract.
+///
+/// Custom errors are looked up in the ABI registered for the contract
+/// and in the standard token errors (ERC-6093).
+pub(crate) fn decode(contract: Address, data: &[u8]) -> Option<RevertReason> {
+    let (selector, args) = (data.get(..4)?, &data[4..]);
+
+    if selector == ERROR_SELECTOR {
+        let message = abi::decode(&[ParamType::String], args)
+            .ok()?
+            .pop()?
+            .into_string()?;
+        return Some(RevertReason::Error { message });
+    }
+
+    if selector == PANIC_SELECTOR {
+        let code = abi::decode(&[ParamType::Uint(256)], args)
+            .ok()?
+            .pop()?
+            .into_uint()?;
+        return Some(RevertReason::Panic {
+            code: code.into(),
+            description: panic_description(code).to_owned(),
+        });
+    }
+
+    let registered = registered_abi(contract);
+    [registered.as_ref(), Some(&*ERC6093_ABI)]
+        .into_iter()
+        .flatten()
+        .flat_map(|abi| abi.errors())
+        .find(|error| error_selector(error) == selector)
+        .and_then(|error| decode_custom(error, args))
+}
+
+fn decode_custom(error: &AbiError, args: &[u8]) -> Option<RevertReason> {
+    let kinds: Vec<ParamType> = error
+        .inputs
+        .iter()
+        .map(|param| param.kind.clone())
+        .collect();
+    let tokens = abi::decode(&kinds, args).ok()?;
+
+    Some(RevertReason::Custom {
+        name: error.name.clone(),
+        args: tokens.iter().map(ToString::to_string).collect(),
+    })
+}
+
+fn error_selector(error: &AbiError) -> [u8; 4] {
+    let kinds: Vec<String> = error
+        .inputs
+        .iter()
+        .map(|param| param.kind.to_string())
+        .collect();
+    id(format!("{}({})", error.name, kinds.join(",")))
+}
+
+/// Description of the panic codes emitted by the Solidity compiler.
+fn panic_description(code: U256) -> &'static str {
+    if code > U256::from(u8::MAX) {
+        return "unknown panic code";
+    }
+
+    match code.as_u32() {
+        0x00 => "generic compiler inserted panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow or underflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array encoding",
+        0x31 => "pop on an empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to a zero-initialized function",
+        _ => "unknown panic code",
+    }
+}
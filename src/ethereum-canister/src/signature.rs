@@ -0,0 +1,17 @@
+use ethers_core::types::{Address, Signature, H256};
+use eyre::Result;
+
+/// Recovers the address which signed the given message hash.
+pub(crate) fn recover_address(message_hash: H256, signature: &Signature) -> Result<Address> {
+    let address = signature.recover(message_hash)?;
+    Ok(address)
+}
+
+/// Checks if the EIP-191 personal `message` was signed by the `address`.
+pub(crate) fn verify_personal_message(
+    address: Address,
+    message: &str,
+    signature: &Signature,
+) -> bool {
+    signature.verify(message, address).is_ok()
+}
@@ -0,0 +1,285 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use candid::Principal;
+use ethers_core::types::{Address, Signature};
+use ethers_core::utils::{hash_message, to_checksum};
+use eyre::{Report, Result, WrapErr};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::rngs::OsRng;
+
+use crate::errors::InvalidInput;
+use crate::quotas::QuotaExceeded;
+use crate::signature;
+use crate::stable_memory::{
+    init_stable_btree_map, StableBTreeMap, StorableAddress, StorablePrincipal, SIWE_LINKS_ID,
+};
+use crate::{erc1271, helios, random};
+
+/// How long the prepared message can be used to log in.
+const MESSAGE_TTL_NANOS: u64 = 5 * 60 * 1_000_000_000;
+const NONCE_LENGTH: usize = 17;
+/// Older pending logins of the principal are dropped when it prepares more messages.
+const MAX_PENDING_LOGINS_PER_PRINCIPAL: usize = 5;
+/// Upper bound of the pending logins kept in the heap.
+const MAX_PENDING_LOGINS: usize = 10_000;
+
+thread_local! {
+    static PENDING_LOGINS: RefCell<BTreeMap<String, PendingLogin>> = RefCell::new(BTreeMap::new());
+    static LINKED_ADDRESSES: RefCell<StableBTreeMap<StorablePrincipal, StorableAddress>> =
+        RefCell::new(init_stable_btree_map(SIWE_LINKS_ID));
+}
+
+struct PendingLogin {
+    principal: Principal,
+    message: String,
+    expires_at: u64,
+}
+
+/// Creates an EIP-4361 message for `address` to be signed by the user.
+///
+/// The message is bound to the `principal` and can only be used once before it expires.
+pub(crate) async fn prepare(
+    principal: Principal,
+    address: Address,
+    domain: &str,
+    chain_id: u64,
+) -> Result<String> {
+    ensure_not_anonymous(principal)?;
+
+    let nonce = {
+        let _guard = random::enter().await;
+        Alphanumeric.sample_string(&mut OsRng, NONCE_LENGTH)
+    };
+
+    let issued_at = ic_cdk::api::time();
+    let expires_at = issued_at + MESSAGE_TTL_NANOS;
+
+    let message = format!(
+        "{domain} wants you to sign in with your Ethereum account:\n\
+         {address}\n\
+         \n\
+         Link this address to the Internet Computer principal {principal}.\n\
+         \n\
+         URI: https://{domain}\n\
+         Version: 1\n\
+         Chain ID: {chain_id}\n\
+         Nonce: {nonce}\n\
+         Issued At: {issued_at}\n\
+         Expiration Time: {expires_at}",
+        address = to_checksum(&address, None),
+        issued_at = format_timestamp(issued_at),
+        expires_at = format_timestamp(expires_at),
+    );
+
+    PENDING_LOGINS.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        pending.retain(|_, login| login.expires_at > issued_at);
+
+        let mut own: Vec<_> = pending
+            .iter()
+            .filter(|(_, login)| login.principal == principal)
+            .map(|(nonce, login)| (login.expires_at, nonce.clone()))
+            .collect();
+        if own.len() >= MAX_PENDING_LOGINS_PER_PRINCIPAL {
+            own.sort_unstable();
+            for (_, nonce) in &own[..=own.len() - MAX_PENDING_LOGINS_PER_PRINCIPAL] {
+                pending.remove(nonce);
+            }
+        }
+
+        if pending.len() >= MAX_PENDING_LOGINS {
+            let oldest_expiry = pending.values().map(|login| login.expires_at).min();
+            let retry_after_secs = oldest_expiry
+                .unwrap_or(expires_at)
+                .saturating_sub(issued_at)
+                / 1_000_000_000
+                + 1;
+            return Err(QuotaExceeded { retry_after_secs });
+        }
+
+        pending.insert(
+            nonce,
+            PendingLogin {
+                principal,
+                message: message.clone(),
+                expires_at,
+            },
+        );
+        Ok(())
+    })?;
+
+    Ok(message)
+}
+
+/// Verifies the signed EIP-4361 `message` and links the `principal` to the signer's address.
+///
+/// Signatures of smart contract wallets are validated with ERC-1271 if the helios client is running.
+pub(crate) async fn login(
+    principal: Principal,
+    message: &str,
+    signature: &[u8],
+    domain: &str,
+    chain_id: u64,
+) -> Result<Address> {
+    ensure_not_anonymous(principal)?;
+    let fields = parse_message(message)?;
+
+    // check the principal before using up the nonce, so that others can't invalidate the message
+    let pending = PENDING_LOGINS.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        match pending.get(fields.nonce) {
+            None => Err(invalid(format!(
+                "Unknown or already used nonce: {}",
+                fields.nonce
+            ))),
+            Some(login) if login.principal != principal => {
+                Err(invalid("Message was prepared for a different principal"))
+            }
+            Some(_) => Ok(pending
+                .remove(fields.nonce)
+                .expect("pending login disappeared")),
+        }
+    })?;
+
+    if fields.domain != domain {
+        return Err(invalid(format!(
+            "Domain mismatch: expected {domain}, got {}",
+            fields.domain
+        )));
+    }
+    if fields.chain_id != chain_id {
+        return Err(invalid(format!(
+            "Chain id mismatch: expected {chain_id}, got {}",
+            fields.chain_id
+        )));
+    }
+    if ic_cdk::api::time() >= pending.expires_at {
+        return Err(invalid("Message expired"));
+    }
+    if pending.message != message {
+        return Err(invalid("Message differs from the prepared one"));
+    }
+
+    if !verify_signature(fields.address, message, signature).await? {
+        return Err(invalid(format!(
+            "Invalid signature for {:?}",
+            fields.address
+        )));
+    }
+
+    link(principal, fields.address);
+
+    Ok(fields.address)
+}
+
+/// The anonymous principal is shared by everyone, so nobody can own its link.
+fn ensure_not_anonymous(principal: Principal) -> Result<()> {
+    if principal == Principal::anonymous() {
+        return Err(invalid("The anonymous principal can't sign in"));
+    }
+    Ok(())
+}
+
+async fn verify_signature(address: Address, message: &str, signature: &[u8]) -> Result<bool> {
+    if let Ok(eoa_signature) = Signature::try_from(signature) {
+        if signature::verify_personal_message(address, message, &eoa_signature) {
+            return Ok(true);
+        }
+    }
+
+    if helios::try_client().is_none() {
+        return Ok(false);
+    }
+
+    erc1271::is_valid_signature(address, hash_message(message), signature.to_vec())
+        .await
+        .wrap_err("ERC-1271 signature validation failed")
+}
+
+fn link(principal: Principal, address: Address) {
+    LINKED_ADDRESSES.with(|links| {
+        links
+            .borrow_mut()
+            .insert(StorablePrincipal(principal), StorableAddress(address))
+    });
+}
+
+pub(crate) fn linked_address(principal: Principal) -> Option<Address> {
+    LINKED_ADDRESSES.with(|links| {
+        links
+            .borrow()
+            .get(&StorablePrincipal(principal))
+            .map(|address| address.0)
+    })
+}
+
+struct MessageFields<'a> {
+    domain: &'a str,
+    address: Address,
+    chain_id: u64,
+    nonce: &'a str,
+}
+
+fn parse_message(message: &str) -> Result<MessageFields<'_>> {
+    let mut lines = message.lines();
+
+    let domain = lines
+        .next()
+        .and_then(|line| line.strip_suffix(" wants you to sign in with your Ethereum account:"))
+        .ok_or_else(|| invalid("Missing domain"))?;
+    let address = lines
+        .next()
+        .ok_or_else(|| invalid("Missing address"))?
+        .parse()
+        .map_err(|_| invalid("Invalid address"))?;
+
+    let field = |name: &str| {
+        let prefix = format!("{name}: ");
+        message
+            .lines()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .ok_or_else(|| invalid(format!("Missing field: {name}")))
+    };
+
+    let chain_id = field("Chain ID")?
+        .parse()
+        .map_err(|_| invalid("Invalid chain id"))?;
+    let nonce = field("Nonce")?;
+
+    Ok(MessageFields {
+        domain,
+        address,
+        chain_id,
+        nonce,
+    })
+}
+
+fn invalid(message: impl Into<String>) -> Report {
+    InvalidInput(message.into()).into()
+}
+
+/// Formats nanoseconds since the unix epoch as an RFC 3339 UTC timestamp.
+fn format_timestamp(nanos: u64) -> String {
+    let secs = nanos / 1_000_000_000;
+    let days = (secs / 86_400) as i64;
+    let secs_of_day = secs % 86_400;
+
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60,
+    )
+}
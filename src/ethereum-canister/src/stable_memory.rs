@@ -1,14 +1,38 @@
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::thread::LocalKey;
 
+use candid::{CandidType, Principal};
+use ethers_core::types::Address;
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{Cell, DefaultMemoryImpl, Storable};
+use ic_stable_structures::{BTreeMap, BoundedStorable, Cell, DefaultMemoryImpl, Storable};
+use serde::de::DeserializeOwned;
 
 // IDs per variable in stable memory
 pub(crate) const LAST_CONSENSUS_RPC_URL_ID: MemoryId = MemoryId::new(0);
 pub(crate) const LAST_EXECUTION_RPC_URL_ID: MemoryId = MemoryId::new(1);
 pub(crate) const LAST_CHECKPOINT_ID: MemoryId = MemoryId::new(2);
 pub(crate) const LAST_NETWORK_ID: MemoryId = MemoryId::new(3);
+pub(crate) const ECDSA_KEY_NAME_ID: MemoryId = MemoryId::new(4);
+pub(crate) const SIWE_DOMAIN_ID: MemoryId = MemoryId::new(5);
+pub(crate) const SIWE_LINKS_ID: MemoryId = MemoryId::new(6);
+pub(crate) const MAX_HEAD_AGE_ID: MemoryId = MemoryId::new(7);
+pub(crate) const ADMINS_ID: MemoryId = MemoryId::new(8);
+pub(crate) const EXECUTION_RPC_URLS_ID: MemoryId = MemoryId::new(9);
+pub(crate) const CONSENSUS_RPC_URLS_ID: MemoryId = MemoryId::new(10);
+pub(crate) const NETWORK_ID: MemoryId = MemoryId::new(11);
+pub(crate) const FINALIZED_ONLY_ID: MemoryId = MemoryId::new(12);
+pub(crate) const REGISTERED_ABIS_ID: MemoryId = MemoryId::new(13);
+pub(crate) const PRICE_LIST_ID: MemoryId = MemoryId::new(14);
+pub(crate) const LEDGER_ID: MemoryId = MemoryId::new(15);
+pub(crate) const PREPAID_BALANCES_ID: MemoryId = MemoryId::new(16);
+pub(crate) const QUOTA_TIERS_ID: MemoryId = MemoryId::new(17);
+// 18 held the quota assignments before they moved to QUOTA_ASSIGNMENTS_ID
+pub(crate) const CALLER_ALLOWLIST_ID: MemoryId = MemoryId::new(19);
+pub(crate) const CONTRACT_ALLOWLIST_ID: MemoryId = MemoryId::new(20);
+pub(crate) const CHECKPOINT_SYNC_URLS_ID: MemoryId = MemoryId::new(21);
+pub(crate) const CHECKPOINT_QUORUM_ID: MemoryId = MemoryId::new(22);
+pub(crate) const QUOTA_ASSIGNMENTS_ID: MemoryId = MemoryId::new(23);
 
 thread_local! {
     static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
@@ -16,6 +40,7 @@ thread_local! {
 }
 
 pub(crate) type StableCell<T> = Cell<T, VirtualMemory<DefaultMemoryImpl>>;
+pub(crate) type StableBTreeMap<K, V> = BTreeMap<K, V, VirtualMemory<DefaultMemoryImpl>>;
 
 pub(crate) fn memory_of(id: MemoryId) -> VirtualMemory<DefaultMemoryImpl> {
     MEMORY_MANAGER.with(|mngr| mngr.borrow().get(id))
@@ -39,6 +64,16 @@ where
     init_stable_cell(id, T::default())
 }
 
+/// If memory of `id` is already initialized then that content will be used, otherwise an empty
+/// map is created.
+pub(crate) fn init_stable_btree_map<K, V>(id: MemoryId) -> StableBTreeMap<K, V>
+where
+    K: BoundedStorable + Ord + Clone,
+    V: BoundedStorable,
+{
+    StableBTreeMap::init(memory_of(id))
+}
+
 pub(crate) fn save_static_string(
     cell: &'static LocalKey<RefCell<StableCell<String>>>,
     s: impl Into<Option<String>>,
@@ -63,3 +98,116 @@ pub(crate) fn load_static_string(
         Some(s)
     }
 }
+
+pub(crate) fn save_static_strings(
+    cell: &'static LocalKey<RefCell<StableCell<Candid<Vec<String>>>>>,
+    strings: Vec<String>,
+) {
+    cell.with(|val| {
+        val.borrow_mut()
+            .set(Candid(strings))
+            .expect("failed to save strings")
+    });
+}
+
+pub(crate) fn load_static_strings(
+    cell: &'static LocalKey<RefCell<StableCell<Candid<Vec<String>>>>>,
+) -> Vec<String> {
+    cell.with(|val| val.borrow().get().0.clone())
+}
+
+/// Candid encoded value, used for the configuration kept in a [`StableCell`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Candid<T>(pub(crate) T);
+
+impl<T> Storable for Candid<T>
+where
+    T: CandidType + DeserializeOwned,
+{
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(candid::encode_one(&self.0).expect("failed to encode value"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Candid(candid::decode_one(&bytes).expect("failed to decode value"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct StorablePrincipal(pub(crate) Principal);
+
+impl Storable for StorablePrincipal {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StorablePrincipal(Principal::from_slice(&bytes))
+    }
+}
+
+impl BoundedStorable for StorablePrincipal {
+    const MAX_SIZE: u32 = 29;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct StorableAddress(pub(crate) Address);
+
+impl Storable for StorableAddress {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StorableAddress(Address::from_slice(&bytes))
+    }
+}
+
+impl BoundedStorable for StorableAddress {
+    const MAX_SIZE: u32 = 20;
+    const IS_FIXED_SIZE: bool = true;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct StorableU128(pub(crate) u128);
+
+impl Storable for StorableU128 {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(self.0.to_le_bytes().to_vec())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut le_bytes = [0u8; 16];
+        le_bytes.copy_from_slice(&bytes);
+        StorableU128(u128::from_le_bytes(le_bytes))
+    }
+}
+
+impl BoundedStorable for StorableU128 {
+    const MAX_SIZE: u32 = 16;
+    const IS_FIXED_SIZE: bool = true;
+}
+
+/// Short utf-8 name, eg. of a quota tier.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct StorableName(pub(crate) String);
+
+impl StorableName {
+    pub(crate) const MAX_LENGTH: usize = 64;
+}
+
+impl Storable for StorableName {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StorableName(String::from_utf8(bytes.into_owned()).expect("invalid utf-8 name"))
+    }
+}
+
+impl BoundedStorable for StorableName {
+    const MAX_SIZE: u32 = Self::MAX_LENGTH as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
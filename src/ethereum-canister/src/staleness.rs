@@ -0,0 +1,73 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use eyre::{eyre, Result};
+use helios_common::types::BlockTag;
+
+use crate::helios;
+use crate::stable_memory::{init_stable_cell_default, StableCell, MAX_HEAD_AGE_ID};
+
+thread_local! {
+    // Zero means that the guard is disabled
+    static MAX_HEAD_AGE_SECS: RefCell<StableCell<u64>> = RefCell::new(init_stable_cell_default(MAX_HEAD_AGE_ID));
+}
+
+/// Returned when the latest verified header is older than the configured maximum.
+#[derive(Debug)]
+pub(crate) struct StaleHead {
+    pub(crate) head_age_secs: u64,
+    pub(crate) max_head_age_secs: u64,
+}
+
+impl fmt::Display for StaleHead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stale head: latest verified header is {}s old, maximum allowed age is {}s",
+            self.head_age_secs, self.max_head_age_secs
+        )
+    }
+}
+
+impl std::error::Error for StaleHead {}
+
+pub(crate) fn max_head_age() -> Option<u64> {
+    let secs = MAX_HEAD_AGE_SECS.with(|cell| *cell.borrow().get());
+    (secs != 0).then_some(secs)
+}
+
+pub(crate) fn set_max_head_age(secs: Option<u64>) {
+    MAX_HEAD_AGE_SECS.with(|cell| {
+        cell.borrow_mut()
+            .set(secs.unwrap_or_default())
+            .expect("failed to save max head age")
+    });
+}
+
+/// Fails with [`StaleHead`] if the latest verified header is older than the configured maximum.
+///
+/// The latest header is checked in the finalized only mode as well, the finalized one always
+/// lags about two epochs behind and its age says nothing about the sync being stuck.
+pub(crate) async fn ensure_fresh() -> Result<()> {
+    let Some(max_head_age_secs) = max_head_age() else {
+        return Ok(());
+    };
+
+    let head = helios::client()?
+        .get_block_by_number(BlockTag::Latest, false)
+        .await?
+        .ok_or_else(|| eyre!("Head block not found"))?;
+
+    let now_secs = ic_cdk::api::time() / 1_000_000_000;
+    let head_age_secs = now_secs.saturating_sub(head.timestamp);
+
+    if head_age_secs > max_head_age_secs {
+        return Err(StaleHead {
+            head_age_secs,
+            max_head_age_secs,
+        }
+        .into());
+    }
+
+    Ok(())
+}
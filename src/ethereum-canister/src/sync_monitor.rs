@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+use std::time::Duration;
+
+use eyre::{eyre, Result};
+use helios_common::types::BlockTag;
+use ic_cdk_timers::{clear_timer, set_timer_interval, TimerId};
+use interface::{Network, SyncStatus};
+use log::{error, warn};
+
+use crate::{finality, helios, networks};
+
+/// Same as the Ethereum slot time, so the head is expected to advance on every poll.
+const POLL_INTERVAL: Duration = Duration::from_secs(12);
+const SECONDS_PER_SLOT: u64 = 12;
+const SLOTS_PER_SYNC_COMMITTEE_PERIOD: u64 = 32 * 256;
+/// Number of polls without progress after which the next consensus endpoint is used.
+const CONSENSUS_FAILOVER_POLLS: u32 = 5;
+
+thread_local! {
+    static STATE: RefCell<MonitorState> = RefCell::new(MonitorState::default());
+}
+
+#[derive(Default)]
+struct MonitorState {
+    timer: Option<TimerId>,
+    network: Option<Network>,
+    last_block: Option<u64>,
+    last_advance: Option<u64>,
+    consecutive_failures: u32,
+}
+
+/// Starts watching the progress of the background sync of the helios client.
+///
+/// When the client is restarted on the same network, e.g. on a failover, the progress
+/// is kept so that a sync stalled across the restarts keeps counting the failures.
+pub(crate) fn start(network: Network) {
+    stop();
+
+    let timer = set_timer_interval(POLL_INTERVAL, poll);
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if state.network.as_ref() != Some(&network) {
+            *state = MonitorState {
+                timer: None,
+                network: Some(network),
+                last_block: None,
+                last_advance: Some(ic_cdk::api::time()),
+                consecutive_failures: 0,
+            };
+        }
+        state.timer = Some(timer);
+    });
+}
+
+pub(crate) fn stop() {
+    STATE.with(|state| {
+        if let Some(timer) = state.borrow_mut().timer.take() {
+            clear_timer(timer);
+        }
+    });
+}
+
+fn poll() {
+    let block_num = match helios::try_client() {
+        Some(client) => client.get_block_number(),
+        None if helios::is_restarting() => return,
+        // a restart failed, counted as a failure so that the client is started again
+        None => Err(eyre!("Client is not running")),
+    };
+
+    let fail_over = STATE.with(|state| {
+        let mut state = state.borrow_mut();
+
+        match block_num {
+            Ok(block_num) if state.last_block.map_or(true, |last| block_num > last) => {
+                state.last_block = Some(block_num);
+                state.last_advance = Some(ic_cdk::api::time());
+                state.consecutive_failures = 0;
+            }
+            Ok(block_num) => {
+                state.consecutive_failures += 1;
+                warn!(
+                    "Head didn't advance past block {block_num} for {} polls",
+                    state.consecutive_failures
+                );
+            }
+            Err(e) => {
+                state.consecutive_failures += 1;
+                warn!("Reading the head failed: {e}");
+            }
+        }
+
+        state.consecutive_failures > 0 && state.consecutive_failures % CONSENSUS_FAILOVER_POLLS == 0
+    });
+
+    if fail_over {
+        ic_cdk::spawn(async {
+            if let Err(e) = helios::fail_over_consensus().await {
+                error!("Consensus failover failed: {e:?}");
+            }
+        });
+    }
+}
+
+pub(crate) async fn status() -> Result<SyncStatus> {
+    let client = helios::client()?;
+
+    let (network, last_advance, consecutive_failures) = STATE.with(|state| {
+        let state = state.borrow();
+        (
+            state.network.clone(),
+            state.last_advance,
+            state.consecutive_failures,
+        )
+    });
+    let network = network.ok_or_else(|| eyre!("Sync monitor not started"))?;
+
+    let optimistic = client
+        .get_block_by_number(BlockTag::Latest, false)
+        .await?
+        .ok_or_else(|| eyre!("Latest block not found"))?;
+    let finalized = client
+        .get_block_by_number(BlockTag::Finalized, false)
+        .await?
+        .ok_or_else(|| eyre!("Finalized block not found"))?;
+
+    let optimistic_slot = slot_at(&network, optimistic.timestamp);
+    let finalized_slot = slot_at(&network, finalized.timestamp);
+
+    Ok(SyncStatus {
+        optimistic_slot,
+        optimistic_block: optimistic.number,
+        finalized_slot,
+        finalized_block: finalized.number,
+        last_advance_timestamp: last_advance,
+        consecutive_failures,
+        sync_committee_period: finalized_slot / SLOTS_PER_SYNC_COMMITTEE_PERIOD,
+        checkpoint: client.get_last_checkpoint(),
+        finalized_only: finality::finalized_only(),
+    })
+}
+
+fn slot_at(network: &Network, timestamp: u64) -> u64 {
+    timestamp.saturating_sub(networks::genesis_time(network)) / SECONDS_PER_SLOT
+}
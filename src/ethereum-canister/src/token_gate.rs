@@ -0,0 +1,102 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use ethers_core::types::Address;
+use eyre::Result;
+use interface::TokenGateRule;
+
+use crate::errors::{self, InvalidInput};
+use crate::{erc1155, erc20, erc721};
+
+/// Maximum nesting of the composite rules, the top level rule is at depth 1.
+const MAX_DEPTH: usize = 8;
+
+/// Checks that the rule is nested at most [`MAX_DEPTH`] levels deep.
+///
+/// The rules are walked without recursion, as they come from the callers.
+pub(crate) fn validate(rule: &TokenGateRule) -> Result<()> {
+    let mut rules = vec![(rule, 1)];
+    while let Some((rule, depth)) = rules.pop() {
+        if depth > MAX_DEPTH {
+            return Err(InvalidInput(format!(
+                "Token gate rules can be nested at most {MAX_DEPTH} levels deep"
+            ))
+            .into());
+        }
+        if let TokenGateRule::All(nested) | TokenGateRule::Any(nested) = rule {
+            rules.extend(nested.iter().map(|rule| (rule, depth + 1)));
+        }
+    }
+    Ok(())
+}
+
+/// Contracts referenced by the rule and its nested rules.
+pub(crate) fn contracts(rule: &TokenGateRule) -> Vec<Address> {
+    match rule {
+        TokenGateRule::Erc20MinBalance { contract, .. }
+        | TokenGateRule::Erc721OwnsAny { contract }
+        | TokenGateRule::Erc721OwnsToken { contract, .. }
+        | TokenGateRule::Erc1155MinBalance { contract, .. } => vec![(*contract).into()],
+        TokenGateRule::All(rules) | TokenGateRule::Any(rules) => {
+            rules.iter().flat_map(contracts).collect()
+        }
+    }
+}
+
+/// Checks if the `account` satisfies the `rule` using the verified state.
+///
+/// Composite rules are evaluated lazily, stopping at the first rule that decides the result.
+/// The rule must be [`validate`]d first.
+pub(crate) fn evaluate(
+    rule: &TokenGateRule,
+    account: Address,
+) -> Pin<Box<dyn Future<Output = Result<bool>> + '_>> {
+    Box::pin(async move {
+        match rule {
+            TokenGateRule::Erc20MinBalance {
+                contract,
+                min_balance,
+            } => {
+                let balance = erc20::balance_of((*contract).into(), account).await?;
+                Ok(balance >= (*min_balance).into())
+            }
+            TokenGateRule::Erc721OwnsAny { contract } => {
+                let balance = erc721::balance_of((*contract).into(), account).await?;
+                Ok(!balance.is_zero())
+            }
+            TokenGateRule::Erc721OwnsToken { contract, token_id } => {
+                // `ownerOf` reverts for tokens that don't exist, nobody owns them
+                match erc721::owner_of((*contract).into(), (*token_id).into()).await {
+                    Ok(owner) => Ok(owner == account),
+                    Err(err) if errors::is_revert(&err) => Ok(false),
+                    Err(err) => Err(err),
+                }
+            }
+            TokenGateRule::Erc1155MinBalance {
+                contract,
+                token_id,
+                min_balance,
+            } => {
+                let balance =
+                    erc1155::balance_of((*contract).into(), account, (*token_id).into()).await?;
+                Ok(balance >= (*min_balance).into())
+            }
+            TokenGateRule::All(rules) => {
+                for rule in rules {
+                    if !evaluate(rule, account).await? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            TokenGateRule::Any(rules) => {
+                for rule in rules {
+                    if evaluate(rule, account).await? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    })
+}
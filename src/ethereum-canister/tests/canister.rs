@@ -1,11 +1,19 @@
-use candid::Nat;
+use candid::{CandidType, Nat, Principal, Reserved};
 use contracts_abi::erc20::BalanceOfCall;
 use ethers_core::abi::AbiEncode;
-use interface::EstimateGasRequest;
+use interface::{
+    AssignQuotaTierRequest, CallCost, CustomNetwork, Erc20BalanceOfRequest,
+    EstimateCallCostRequest, EstimateGasRequest, EthError, Fork, LedgerConfig, Network, PriceList,
+    Quota, QuotaTier, ReconfigureRequest, RpcEndpointStats, SetupRequest, SyncStatus,
+    WithdrawTokensRequest, U256,
+};
 
 mod test_canister;
 
-use crate::test_canister::{call, setup_ethereum_canister};
+use crate::test_canister::{
+    call, sample_price_list, setup_ethereum_canister, setup_ethereum_canister_with, setup_ledger,
+    setup_request, usdt_balance_request, TestCanister, LEDGER_FEE,
+};
 
 #[test]
 fn get_block_number() {
@@ -66,10 +74,55 @@ mod erc20 {
         };
         let _: (U256,) = call!(canister, "erc20_balance_of", request).unwrap();
     }
+
+    #[test]
+    fn balance_of_cached() {
+        // the finalized head moves once an epoch, so the second call hits the same block
+        let canister = setup_ethereum_canister_with(SetupRequest {
+            finalized_only: Some(true),
+            ..setup_request()
+        });
+
+        let contract = "0xdAC17F958D2ee523a2206206994597C13D831ec7"
+            .parse()
+            .unwrap();
+        let account = "0xF977814e90dA44bFA03b6295A0616a897441aceC"
+            .parse()
+            .unwrap();
+        // the quota counts the outcalls of the calls
+        let tiers = vec![QuotaTier {
+            name: "default".to_owned(),
+            requests_per_minute: 100,
+            outcalls_per_hour: 10_000,
+        }];
+        let _: () = call!(canister, "set_quota_tiers", tiers).unwrap();
+        let caller = canister.caller_principal();
+
+        let request = Erc20BalanceOfRequest { contract, account };
+        let _: (U256,) = call!(canister, "erc20_balance_of", request.clone()).unwrap();
+        let (quota,): (Option<Quota>,) = call!(canister, "get_quota", caller).unwrap();
+        let outcalls = quota.unwrap().outcalls_in_current_hour;
+        assert!(outcalls > 0);
+
+        let _: (U256,) = call!(canister, "erc20_balance_of", request).unwrap();
+        let (quota,): (Option<Quota>,) = call!(canister, "get_quota", caller).unwrap();
+        assert_eq!(quota.unwrap().outcalls_in_current_hour, outcalls);
+
+        // the dry run doesn't use the cache, it makes the outcalls of an uncached call
+        let request = EstimateCallCostRequest {
+            contract,
+            data: BalanceOfCall {
+                account: account.into(),
+            }
+            .encode(),
+        };
+        let (cost,): (CallCost,) = call!(canister, "estimate_call_cost", request).unwrap();
+        assert!(cost.estimated_outcalls > 0);
+    }
 }
 
 mod erc721 {
-    use interface::{Address, Erc721OwnerOfRequest};
+    use interface::{Address, Erc721OwnerOfRequest, RevertReason};
 
     use super::*;
 
@@ -86,4 +139,896 @@ mod erc721 {
 
         let _: (Address,) = call!(canister, "erc721_owner_of", request).unwrap();
     }
+
+    #[test]
+    fn owner_of_nonexistent_token_reverts() {
+        let canister = setup_ethereum_canister();
+
+        let request = Erc721OwnerOfRequest {
+            contract: "0x5Af0D9827E0c53E4799BB226655A1de152A425a5" // milady
+                .parse()
+                .unwrap(),
+            token_id: 1_000_000_u32.into(),
+        };
+
+        let (result,): (Result<Address, EthError>,) =
+            call!(canister, "erc721_owner_of_result", request).unwrap();
+        let Err(EthError::ExecutionReverted {
+            reason: Some(RevertReason::Error { message }),
+            ..
+        }) = result
+        else {
+            panic!("unexpected result: {result:?}");
+        };
+        assert!(message.contains("nonexistent token"));
+    }
+}
+
+#[test]
+fn max_head_age() {
+    let canister = setup_ethereum_canister();
+
+    // slots are 12 seconds so the head is always older than a second
+    let _: () = call!(canister, "set_max_head_age", Some(1_u64)).unwrap();
+    let err = call!(canister, "get_block_number")
+        .map(|_: (Nat,)| ())
+        .unwrap_err();
+    assert!(err.to_string().contains("Stale head"));
+
+    let _: () = call!(canister, "set_max_head_age", Some(10 * 60_u64)).unwrap();
+    let _: (Nat,) = call!(canister, "get_block_number").unwrap();
+
+    let _: () = call!(canister, "set_max_head_age", None::<u64>).unwrap();
+    let _: (Nat,) = call!(canister, "get_block_number").unwrap();
+}
+
+#[test]
+fn reconfigure() {
+    let canister = setup_ethereum_canister();
+
+    let (block_before,): (Nat,) = call!(canister, "get_block_number").unwrap();
+
+    let request = ReconfigureRequest {
+        consensus_rpc_urls: None,
+        execution_rpc_urls: Some(vec![
+            "https://eth.llamarpc.com".to_owned(),
+            "https://ethereum.publicnode.com".to_owned(),
+        ]),
+    };
+    let _: () = call!(canister, "reconfigure", request).unwrap();
+
+    let (block_after,): (Nat,) = call!(canister, "get_block_number").unwrap();
+    assert!(block_after >= block_before);
+}
+
+#[test]
+fn setup_checkpoint_quorum_not_reached() {
+    let canister = TestCanister::deploy("ethereum_canister");
+
+    let request = SetupRequest {
+        checkpoint_sync_urls: Some(vec!["https://unreachable.invalid".to_owned()]),
+        checkpoint_quorum: Some(2),
+        ..setup_request()
+    };
+    let err = call!(canister, "setup", request)
+        .map(|_: ()| ())
+        .unwrap_err();
+    assert!(err.to_string().contains("checkpoint sources agree"));
+}
+
+#[test]
+fn setup_custom_network() {
+    let fork = |epoch, version: &str| Fork {
+        epoch,
+        version: version.to_owned(),
+    };
+    // mainnet parameters given as a custom network
+    let network = CustomNetwork {
+        chain_id: 1,
+        genesis_time: 1606824023,
+        genesis_validators_root:
+            "0x4b363db94e286120d76eb905340fdd4e54bfe9f06bf33ff6cf5ad27f511bfe95"
+                .parse()
+                .unwrap(),
+        genesis: fork(0, "0x00000000"),
+        altair: fork(74240, "0x01000000"),
+        bellatrix: fork(144896, "0x02000000"),
+        capella: fork(194048, "0x03000000"),
+    };
+    let canister = setup_ethereum_canister_with(SetupRequest {
+        network: Network::Custom(network),
+        ..setup_request()
+    });
+
+    let block_num: (Nat,) = call!(canister, "get_block_number").unwrap();
+    assert!(block_num.0 > 17880732u128);
+}
+
+#[test]
+fn setup_testnets() {
+    let testnets = [
+        (
+            Network::Sepolia,
+            "https://ethereum-sepolia-beacon-api.publicnode.com",
+            "https://ethereum-sepolia.publicnode.com",
+        ),
+        (
+            Network::Holesky,
+            "https://ethereum-holesky-beacon-api.publicnode.com",
+            "https://ethereum-holesky.publicnode.com",
+        ),
+    ];
+
+    for (network, consensus_rpc_url, execution_rpc_url) in testnets {
+        let canister = setup_ethereum_canister_with(SetupRequest {
+            network: network.clone(),
+            consensus_rpc_urls: vec![consensus_rpc_url.to_owned()],
+            execution_rpc_urls: vec![execution_rpc_url.to_owned()],
+            ..setup_request()
+        });
+
+        let (stored,): (Option<Network>,) = call!(canister, "get_network").unwrap();
+        assert_eq!(stored, Some(network.clone()));
+
+        // the chain id the canister uses ends up in the SIWE messages
+        let address: interface::Address = "0xF977814e90dA44bFA03b6295A0616a897441aceC"
+            .parse()
+            .unwrap();
+        let (message,): (String,) = call!(canister, "siwe_prepare", address).unwrap();
+        assert!(message.contains(&format!("Chain ID: {}\n", network.chain_id())));
+
+        let (block_num,): (Nat,) = call!(canister, "get_block_number").unwrap();
+        assert!(block_num.0 > 0_u128);
+    }
+}
+
+#[test]
+fn finalized_only() {
+    let canister = setup_ethereum_canister_with(SetupRequest {
+        finalized_only: Some(true),
+        ..setup_request()
+    });
+
+    let (status,): (SyncStatus,) = call!(canister, "get_sync_status").unwrap();
+    assert!(status.finalized_only);
+
+    let (block_num,): (Nat,) = call!(canister, "get_block_number").unwrap();
+    assert!(block_num >= status.finalized_block);
+    assert!(block_num < status.optimistic_block);
+
+    let request = EstimateGasRequest {
+        from: None,
+        to: "0xdAC17F958D2ee523a2206206994597C13D831ec7"
+            .parse()
+            .unwrap(),
+        gas_limit: None,
+        gas_price: None,
+        value: None,
+        data: None,
+    };
+    let err = call!(canister, "estimate_gas", request.clone())
+        .map(|_: (Nat,)| ())
+        .unwrap_err();
+    assert!(err.to_string().contains("finalized only mode"));
+
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "estimate_gas_result", request).unwrap();
+    assert!(matches!(result, Err(EthError::InvalidInput { .. })));
+}
+
+#[test]
+fn consensus_rpc_failover() {
+    let canister = setup_ethereum_canister();
+
+    let request = ReconfigureRequest {
+        consensus_rpc_urls: Some(vec![
+            "https://unreachable.invalid".to_owned(),
+            "https://www.lightclientdata.org".to_owned(),
+        ]),
+        execution_rpc_urls: None,
+    };
+    let _: () = call!(canister, "reconfigure", request).unwrap();
+
+    let block_num: (Nat,) = call!(canister, "get_block_number").unwrap();
+    assert!(block_num.0 > 17880732u128);
+}
+
+#[test]
+fn get_execution_rpc_stats() {
+    let canister = setup_ethereum_canister();
+
+    let request = usdt_balance_request();
+    let _: (Nat,) = call!(canister, "erc20_balance_of", request).unwrap();
+
+    let (stats,): (Vec<RpcEndpointStats>,) = call!(canister, "get_execution_rpc_stats").unwrap();
+    assert_eq!(stats.len(), 1);
+    assert!(stats[0].current);
+    assert!(stats[0].requests >= 1);
+}
+
+#[test]
+fn get_block_number_result_stale() {
+    let canister = setup_ethereum_canister();
+
+    let _: () = call!(canister, "set_max_head_age", Some(1_u64)).unwrap();
+    let (result,): (Result<Nat, EthError>,) = call!(canister, "get_block_number_result").unwrap();
+    assert!(matches!(result, Err(EthError::Stale { .. })));
+}
+
+#[test]
+fn erc20_balance_of_result_not_initialized() {
+    let canister = TestCanister::deploy("ethereum_canister");
+
+    let request = usdt_balance_request();
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "erc20_balance_of_result", request).unwrap();
+    assert_eq!(result, Err(EthError::NotInitialized));
+}
+
+#[test]
+fn price_list() {
+    let canister = setup_ethereum_canister();
+
+    let price_list = sample_price_list();
+    let _: () = call!(canister, "set_price_list", Some(price_list.clone())).unwrap();
+
+    let (current,): (Option<PriceList>,) = call!(canister, "get_price_list").unwrap();
+    assert_eq!(current, Some(price_list.clone()));
+
+    // the attached cycles have to cover the base fee and an outcall
+    let free = PriceList {
+        min_attached_cycles: 0,
+        ..price_list
+    };
+    let result: eyre::Result<()> = call!(canister, "set_price_list", Some(free));
+    assert!(result.is_err());
+
+    let request = usdt_balance_request();
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "erc20_balance_of_result", request).unwrap();
+    assert_eq!(result, Err(EthError::OutOfCycles));
+}
+
+#[test]
+fn ledger_payments() {
+    let canister = setup_ethereum_canister();
+
+    let price_list = sample_price_list();
+    let _: () = call!(canister, "set_price_list", Some(price_list)).unwrap();
+
+    // there is no ledger deployed locally under the mainnet ICP ledger id
+    let ledger = LedgerConfig {
+        ledger: Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap(),
+        tokens_per_trillion_cycles: 100_000_000,
+        transfer_amount: 10_000_000,
+    };
+    let _: () = call!(canister, "set_ledger", Some(ledger.clone())).unwrap();
+
+    let (current,): (Option<LedgerConfig>,) = call!(canister, "get_ledger").unwrap();
+    assert_eq!(current, Some(ledger));
+
+    let request = usdt_balance_request();
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "erc20_balance_of_result", request).unwrap();
+    assert!(matches!(result, Err(EthError::PaymentFailed { .. })));
+
+    let (balance,): (Nat,) =
+        call!(canister, "get_prepaid_balance", canister.caller_principal()).unwrap();
+    assert_eq!(balance, 0_u128);
+}
+
+#[derive(CandidType)]
+struct Account {
+    owner: Principal,
+    subaccount: Option<Vec<u8>>,
+}
+
+#[derive(CandidType)]
+struct ApproveArgs {
+    from_subaccount: Option<Vec<u8>>,
+    spender: Account,
+    amount: Nat,
+    expected_allowance: Option<Nat>,
+    expires_at: Option<u64>,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+fn ledger_balance(ledger: &TestCanister, owner: Principal) -> Nat {
+    let account = Account {
+        owner,
+        subaccount: None,
+    };
+    let (balance,): (Nat,) = call!(ledger, "icrc1_balance_of", account).unwrap();
+    balance
+}
+
+#[test]
+fn ledger_payments_with_local_ledger() {
+    const MINTED: u128 = 1_000_000_000;
+    const TRANSFER_AMOUNT: u128 = 10_000_000;
+
+    let canister = setup_ethereum_canister();
+    let caller = canister.caller_principal();
+    let ledger = setup_ledger(caller, MINTED);
+
+    let approve = ApproveArgs {
+        from_subaccount: None,
+        spender: Account {
+            owner: canister.canister_id(),
+            subaccount: None,
+        },
+        amount: Nat::from(MINTED),
+        expected_allowance: None,
+        expires_at: None,
+        fee: None,
+        memo: None,
+        created_at_time: None,
+    };
+    let (approved,): (Result<Nat, Reserved>,) = call!(ledger, "icrc2_approve", approve).unwrap();
+    assert!(approved.is_ok());
+
+    let price_list = sample_price_list();
+    let _: () = call!(canister, "set_price_list", Some(price_list)).unwrap();
+    // 50B cycles cost 5M tokens, so the transfer covers the first call with some left over
+    let ledger_config = LedgerConfig {
+        ledger: ledger.canister_id(),
+        tokens_per_trillion_cycles: 100_000_000,
+        transfer_amount: TRANSFER_AMOUNT,
+    };
+    let _: () = call!(canister, "set_ledger", Some(ledger_config)).unwrap();
+
+    let request = usdt_balance_request();
+
+    // the first call pulls the tokens and is paid from them
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "erc20_balance_of_result", request.clone()).unwrap();
+    assert!(result.is_ok());
+
+    let caller_balance = ledger_balance(&ledger, caller);
+    // paid for the approval and the transfer
+    assert_eq!(caller_balance, MINTED - TRANSFER_AMOUNT - 2 * LEDGER_FEE);
+    assert_eq!(
+        ledger_balance(&ledger, canister.canister_id()),
+        TRANSFER_AMOUNT
+    );
+
+    let (prepaid,): (Nat,) = call!(canister, "get_prepaid_balance", caller).unwrap();
+    assert!(prepaid > Nat::from(0_u128) && prepaid < Nat::from(TRANSFER_AMOUNT));
+
+    // the second call is paid from the leftover without another transfer
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "erc20_balance_of_result", request).unwrap();
+    assert!(result.is_ok());
+
+    assert_eq!(ledger_balance(&ledger, caller), caller_balance);
+    let (leftover,): (Nat,) = call!(canister, "get_prepaid_balance", caller).unwrap();
+    assert!(leftover < prepaid);
+
+    // the leftover is still prepaid by the caller and can't be withdrawn
+    let leftover: u128 = leftover.0.try_into().unwrap();
+    let collected = TRANSFER_AMOUNT - leftover;
+    let withdraw = WithdrawTokensRequest {
+        to: caller,
+        amount: collected - LEDGER_FEE + 1,
+    };
+    let result: Result<(Nat,), _> = call!(canister, "withdraw_tokens", withdraw);
+    assert!(result.is_err());
+
+    let withdraw = WithdrawTokensRequest {
+        to: caller,
+        amount: collected - LEDGER_FEE,
+    };
+    let _: (Nat,) = call!(canister, "withdraw_tokens", withdraw).unwrap();
+    assert_eq!(
+        ledger_balance(&ledger, caller),
+        caller_balance + collected - LEDGER_FEE
+    );
+    assert_eq!(ledger_balance(&ledger, canister.canister_id()), leftover);
+}
+
+#[test]
+fn quotas() {
+    let canister = setup_ethereum_canister();
+    let caller = canister.caller_principal();
+
+    let tiers = vec![
+        QuotaTier {
+            name: "default".to_owned(),
+            requests_per_minute: 1,
+            outcalls_per_hour: 1_000,
+        },
+        QuotaTier {
+            name: "internal".to_owned(),
+            requests_per_minute: 100,
+            outcalls_per_hour: 10_000,
+        },
+    ];
+    let _: () = call!(canister, "set_quota_tiers", tiers.clone()).unwrap();
+    let (current,): (Vec<QuotaTier>,) = call!(canister, "get_quota_tiers").unwrap();
+    assert_eq!(current, tiers);
+
+    let long_name = QuotaTier {
+        name: "x".repeat(65),
+        requests_per_minute: 1,
+        outcalls_per_hour: 1,
+    };
+    let result: eyre::Result<()> = call!(canister, "set_quota_tiers", vec![long_name]);
+    assert!(result.is_err());
+
+    let request = usdt_balance_request();
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "erc20_balance_of_result", request.clone()).unwrap();
+    assert!(result.is_ok());
+
+    let (quota,): (Option<Quota>,) = call!(canister, "get_quota", caller).unwrap();
+    let quota = quota.unwrap();
+    assert_eq!(quota.tier, "default");
+    assert!(quota.outcalls_in_current_hour > 0);
+
+    // the second request in a minute is rejected, unless it crossed the minute boundary
+    if quota.requests_in_current_minute == 1 {
+        let (result,): (Result<U256, EthError>,) =
+            call!(canister, "erc20_balance_of_result", request.clone()).unwrap();
+        assert!(matches!(result, Err(EthError::QuotaExceeded { .. })));
+    }
+
+    let assign = AssignQuotaTierRequest {
+        principal: caller,
+        tier: Some("internal".to_owned()),
+    };
+    let _: () = call!(canister, "assign_quota_tier", assign).unwrap();
+    let (result,): (Result<U256, EthError>,) =
+        call!(canister, "erc20_balance_of_result", request).unwrap();
+    assert!(result.is_ok());
+
+    let _: () = call!(canister, "set_quota_tiers", Vec::<QuotaTier>::new()).unwrap();
+    let (quota,): (Option<Quota>,) = call!(canister, "get_quota", caller).unwrap();
+    assert_eq!(quota, None);
+}
+
+#[test]
+fn quotas_count_charged_outcalls() {
+    let canister = setup_ethereum_canister();
+    // calls with cycles attached are made by the wallet
+    let wallet = canister.wallet_principal();
+
+    let price_list = sample_price_list();
+    let _: () = call!(canister, "set_price_list", Some(price_list)).unwrap();
+
+    let tiers = vec![QuotaTier {
+        name: "default".to_owned(),
+        requests_per_minute: 100,
+        outcalls_per_hour: 10_000,
+    }];
+    let _: () = call!(canister, "set_quota_tiers", tiers).unwrap();
+
+    let request = usdt_balance_request();
+    let output = canister
+        .call_with_cycles(100_000_000_000, "erc20_balance_of_result", (request,))
+        .unwrap();
+    let (result,): (Result<U256, EthError>,) = candid::decode_args(&output).unwrap();
+    assert!(result.is_ok());
+
+    // the accepted cycles don't hide the outcalls of the request
+    let (quota,): (Option<Quota>,) = call!(canister, "get_quota", wallet).unwrap();
+    let quota = quota.unwrap();
+    assert!(quota.outcalls_in_current_hour > 0);
+}
+
+#[test]
+fn estimate_call_cost() {
+    let canister = setup_ethereum_canister();
+
+    let account = "0xF977814e90dA44bFA03b6295A0616a897441aceC"
+        .parse()
+        .unwrap();
+    let request = EstimateCallCostRequest {
+        contract: "0xdAC17F958D2ee523a2206206994597C13D831ec7"
+            .parse()
+            .unwrap(),
+        data: BalanceOfCall { account }.encode(),
+    };
+    let (cost,): (CallCost,) = call!(canister, "estimate_call_cost", request.clone()).unwrap();
+    assert!(cost.estimated_outcalls > 0);
+    assert!(cost.outcalls_cycles > 0);
+    assert_eq!(cost.cycles_to_attach, None);
+
+    let price_list = sample_price_list();
+    let _: () = call!(canister, "set_price_list", Some(price_list)).unwrap();
+    let output = canister
+        .call_with_cycles(100_000_000_000, "estimate_call_cost", (request,))
+        .unwrap();
+    let (cost,): (CallCost,) = candid::decode_args(&output).unwrap();
+    assert!(cost.cycles_to_attach.unwrap() > cost.outcalls_cycles);
+}
+
+#[test]
+fn get_sync_status() {
+    let canister = setup_ethereum_canister();
+
+    let (status,): (SyncStatus,) = call!(canister, "get_sync_status").unwrap();
+    assert!(status.finalized_block <= status.optimistic_block);
+    assert!(status.finalized_slot <= status.optimistic_slot);
+    assert!(status.last_advance_timestamp.is_some());
+    assert!(status.checkpoint.is_some());
+}
+
+mod ecdsa {
+    use ethers_core::types::transaction::eip712::TypedData;
+    use ethers_core::types::Signature as EthersSignature;
+    use interface::{SignTypedDataRequest, Signature};
+
+    use super::*;
+
+    const TYPED_DATA: &str = r#"{
+        "types": {
+            "EIP712Domain": [
+                { "name": "name", "type": "string" },
+                { "name": "version", "type": "string" },
+                { "name": "chainId", "type": "uint256" },
+                { "name": "verifyingContract", "type": "address" }
+            ],
+            "Person": [
+                { "name": "name", "type": "string" },
+                { "name": "wallet", "type": "address" }
+            ],
+            "Mail": [
+                { "name": "from", "type": "Person" },
+                { "name": "to", "type": "Person" },
+                { "name": "contents", "type": "string" }
+            ]
+        },
+        "primaryType": "Mail",
+        "domain": {
+            "name": "Ether Mail",
+            "version": "1",
+            "chainId": 1,
+            "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+        },
+        "message": {
+            "from": { "name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826" },
+            "to": { "name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB" },
+            "contents": "Hello, Bob!"
+        }
+    }"#;
+
+    #[test]
+    fn sign_typed_data() {
+        let canister = setup_ethereum_canister();
+        let typed_data: TypedData = serde_json::from_str(TYPED_DATA).unwrap();
+
+        let sign = |derivation_path: &[u8]| {
+            let request = SignTypedDataRequest {
+                derivation_path: vec![derivation_path.to_vec()],
+                typed_data_json: TYPED_DATA.to_owned(),
+            };
+            let (signature,): (Signature,) = call!(canister, "sign_typed_data", request).unwrap();
+            let signature: EthersSignature = signature.into();
+            signature.recover_typed_data(&typed_data).unwrap()
+        };
+
+        let signer = sign(b"orders");
+        assert_eq!(signer, sign(b"orders"));
+        assert_ne!(signer, sign(b"other"));
+    }
+}
+
+mod signature {
+    use ethers_core::k256::ecdsa::SigningKey;
+    use ethers_core::types::{Signature as EthersSignature, H256, U256};
+    use ethers_core::utils::{hash_message, secret_key_to_address};
+    use interface::{Address, RecoverAddressRequest, Signature, VerifyPersonalMessageRequest};
+
+    use super::*;
+
+    const MESSAGE: &str = "Sign in to the Ethereum canister";
+
+    pub(crate) fn sign(key: &SigningKey, hash: H256) -> Signature {
+        let (signature, recovery_id) = key.sign_prehash_recoverable(hash.as_bytes()).unwrap();
+        let (r, s) = signature.split_bytes();
+        EthersSignature {
+            r: U256::from_big_endian(&r),
+            s: U256::from_big_endian(&s),
+            v: 27 + u64::from(recovery_id.to_byte()),
+        }
+        .into()
+    }
+
+    #[test]
+    fn recover_address() {
+        let canister = TestCanister::deploy("ethereum_canister");
+        let key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let hash = H256::repeat_byte(0x42);
+
+        let request = RecoverAddressRequest {
+            message_hash: hash.into(),
+            signature: sign(&key, hash),
+        };
+        let (address,): (Address,) = call!(canister, "recover_address", request).unwrap();
+        assert_eq!(address, secret_key_to_address(&key).into());
+    }
+
+    #[test]
+    fn verify_personal_message() {
+        let canister = TestCanister::deploy("ethereum_canister");
+        let key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let signature = sign(&key, hash_message(MESSAGE));
+
+        let request = VerifyPersonalMessageRequest {
+            address: secret_key_to_address(&key).into(),
+            message: MESSAGE.to_owned(),
+            signature,
+        };
+        let (valid,): (bool,) = call!(canister, "verify_personal_message", request).unwrap();
+        assert!(valid);
+
+        let request = VerifyPersonalMessageRequest {
+            address: secret_key_to_address(&key).into(),
+            message: "Some other message".to_owned(),
+            signature,
+        };
+        let (valid,): (bool,) = call!(canister, "verify_personal_message", request).unwrap();
+        assert!(!valid);
+    }
+}
+
+mod erc1271 {
+    use ethers_core::k256::ecdsa::SigningKey;
+    use ethers_core::types::H256;
+    use interface::Erc1271IsValidSignatureRequest;
+
+    use super::*;
+    use crate::signature::sign;
+
+    fn is_valid_signature(canister: &TestCanister, contract: &str) -> Result<bool, EthError> {
+        let key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let hash = H256::repeat_byte(0x42);
+        let signature: ethers_core::types::Signature = sign(&key, hash).into();
+
+        let request = Erc1271IsValidSignatureRequest {
+            contract: contract.parse().unwrap(),
+            hash: hash.into(),
+            signature: signature.to_vec(),
+        };
+        let (result,): (Result<bool, EthError>,) =
+            call!(canister, "erc1271_is_valid_signature_result", request).unwrap();
+        result
+    }
+
+    #[test]
+    fn is_valid_signature_without_erc1271() {
+        let canister = setup_ethereum_canister();
+
+        // externally owned account, which is not called at all
+        let valid = is_valid_signature(&canister, "0xF977814e90dA44bFA03b6295A0616a897441aceC");
+        assert_eq!(valid, Ok(false));
+
+        // usdt doesn't implement `isValidSignature` and has no fallback function, so it reverts
+        let valid = is_valid_signature(&canister, "0xdAC17F958D2ee523a2206206994597C13D831ec7");
+        assert_eq!(valid, Ok(false));
+    }
+}
+
+mod siwe {
+    use candid::Principal;
+    use ethers_core::k256::ecdsa::SigningKey;
+    use ethers_core::types::Signature as EthersSignature;
+    use ethers_core::utils::{hash_message, secret_key_to_address};
+    use interface::{Address, SiweLoginRequest};
+
+    use super::*;
+    use crate::signature::sign;
+    use crate::test_canister::DEFAULT_SIWE_DOMAIN;
+
+    /// Links the caller's principal to the address of the `key`.
+    pub(crate) fn login(canister: &TestCanister, key: &SigningKey) -> SiweLoginRequest {
+        let address: Address = secret_key_to_address(key).into();
+
+        let (message,): (String,) = call!(canister, "siwe_prepare", address).unwrap();
+        assert!(message.starts_with(DEFAULT_SIWE_DOMAIN));
+
+        let signature: EthersSignature = sign(key, hash_message(&message)).into();
+        let request = SiweLoginRequest {
+            signature: signature.to_vec(),
+            message,
+        };
+        let (linked,): (Address,) = call!(canister, "siwe_login", request.clone()).unwrap();
+        assert_eq!(linked, address);
+
+        request
+    }
+
+    #[test]
+    fn login_and_link() {
+        let canister = setup_ethereum_canister();
+        let key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let address: Address = secret_key_to_address(&key).into();
+
+        let request = login(&canister, &key);
+
+        // nonce can be used only once
+        call!(canister, "siwe_login", request)
+            .map(|_: (Address,)| ())
+            .unwrap_err();
+
+        let principal = canister.caller_principal();
+        let (linked,): (Option<Address>,) =
+            call!(canister, "get_linked_address", principal).unwrap();
+        assert_eq!(linked, Some(address));
+
+        let (linked,): (Option<Address>,) =
+            call!(canister, "get_linked_address", Principal::anonymous()).unwrap();
+        assert_eq!(linked, None);
+    }
+}
+
+mod token_gate {
+    use candid::Principal;
+    use ethers_core::k256::ecdsa::SigningKey;
+    use interface::{CheckTokenGateRequest, TokenGateRule};
+
+    use super::*;
+    use crate::siwe::login;
+
+    #[test]
+    fn check_token_gate() {
+        let canister = setup_ethereum_canister();
+        let key = SigningKey::from_slice(&[7; 32]).unwrap();
+        let usdt_any_balance = TokenGateRule::Erc20MinBalance {
+            contract: "0xdAC17F958D2ee523a2206206994597C13D831ec7" // usdt
+                .parse()
+                .unwrap(),
+            min_balance: 0_u32.into(),
+        };
+        let milady_owner = TokenGateRule::Erc721OwnsToken {
+            contract: "0x5Af0D9827E0c53E4799BB226655A1de152A425a5" // milady
+                .parse()
+                .unwrap(),
+            token_id: 7773_u32.into(),
+        };
+
+        // principal without linked address
+        let request = CheckTokenGateRequest {
+            principal: Principal::anonymous(),
+            rule: usdt_any_balance.clone(),
+        };
+        let (passed,): (bool,) = call!(canister, "check_token_gate", request).unwrap();
+        assert!(!passed);
+
+        login(&canister, &key);
+        let principal = canister.caller_principal();
+
+        let request = CheckTokenGateRequest {
+            principal,
+            rule: TokenGateRule::Any(vec![milady_owner.clone(), usdt_any_balance.clone()]),
+        };
+        let (passed,): (bool,) = call!(canister, "check_token_gate", request).unwrap();
+        assert!(passed);
+
+        let request = CheckTokenGateRequest {
+            principal,
+            rule: TokenGateRule::All(vec![milady_owner]),
+        };
+        let (passed,): (bool,) = call!(canister, "check_token_gate", request).unwrap();
+        assert!(!passed);
+
+        // `ownerOf` reverts for a token that was never minted, which doesn't fail the whole rule
+        let missing_token_owner = TokenGateRule::Erc721OwnsToken {
+            contract: "0x5Af0D9827E0c53E4799BB226655A1de152A425a5" // milady
+                .parse()
+                .unwrap(),
+            token_id: 1_000_000_u32.into(),
+        };
+        let request = CheckTokenGateRequest {
+            principal,
+            rule: TokenGateRule::Any(vec![missing_token_owner, usdt_any_balance.clone()]),
+        };
+        let (passed,): (bool,) = call!(canister, "check_token_gate", request).unwrap();
+        assert!(passed);
+
+        let too_deep = (0..8).fold(usdt_any_balance, |rule, _| TokenGateRule::All(vec![rule]));
+        let request = CheckTokenGateRequest {
+            principal,
+            rule: too_deep,
+        };
+        let result: Result<(bool,), _> = call!(canister, "check_token_gate", request);
+        assert!(result.is_err());
+    }
+}
+
+mod access_control {
+    use candid::Principal;
+
+    use super::*;
+
+    #[test]
+    fn admin_methods() {
+        let canister = TestCanister::deploy("ethereum_canister");
+        let anonymous = Principal::anonymous();
+
+        // only controllers and admins are allowed
+        canister
+            .call_as("anonymous", "set_max_head_age", (None::<u64>,))
+            .unwrap_err();
+        canister
+            .call_as("anonymous", "add_admin", (anonymous,))
+            .unwrap_err();
+
+        let _: () = call!(canister, "add_admin", anonymous).unwrap();
+        let (admins,): (Vec<Principal>,) = call!(canister, "get_admins").unwrap();
+        assert_eq!(admins, vec![anonymous]);
+
+        canister
+            .call_as("anonymous", "set_max_head_age", (None::<u64>,))
+            .unwrap();
+
+        let _: () = call!(canister, "remove_admin", anonymous).unwrap();
+        let (admins,): (Vec<Principal>,) = call!(canister, "get_admins").unwrap();
+        assert!(admins.is_empty());
+
+        canister
+            .call_as("anonymous", "set_max_head_age", (None::<u64>,))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn allowlists() {
+        let canister = setup_ethereum_canister();
+        let anonymous = Principal::anonymous();
+
+        let usdt: interface::Address = "0xdAC17F958D2ee523a2206206994597C13D831ec7"
+            .parse()
+            .unwrap();
+        let shib: interface::Address = "0x95aD61b0a150d79219dCF64E1E6Cc01f0B64C4cE"
+            .parse()
+            .unwrap();
+        let request = Erc20BalanceOfRequest {
+            contract: usdt,
+            account: "0xF977814e90dA44bFA03b6295A0616a897441aceC"
+                .parse()
+                .unwrap(),
+        };
+
+        let _: () = call!(canister, "set_contract_allowlist", Some(vec![shib])).unwrap();
+        let (allowlist,): (Option<Vec<interface::Address>>,) =
+            call!(canister, "get_contract_allowlist").unwrap();
+        assert_eq!(allowlist, Some(vec![shib]));
+
+        let (result,): (Result<U256, EthError>,) =
+            call!(canister, "erc20_balance_of_result", request.clone()).unwrap();
+        assert!(matches!(result, Err(EthError::NotAllowed { .. })));
+
+        let _: () = call!(
+            canister,
+            "set_contract_allowlist",
+            None::<Vec<interface::Address>>
+        )
+        .unwrap();
+        let _: () = call!(
+            canister,
+            "set_caller_allowlist",
+            Some(Vec::<Principal>::new())
+        )
+        .unwrap();
+
+        // admins are always allowed
+        let (result,): (Result<U256, EthError>,) =
+            call!(canister, "erc20_balance_of_result", request.clone()).unwrap();
+        assert!(result.is_ok());
+
+        let output = canister
+            .call_as("anonymous", "erc20_balance_of_result", (request.clone(),))
+            .unwrap();
+        let (result,): (Result<U256, EthError>,) = candid::decode_args(&output).unwrap();
+        assert!(matches!(result, Err(EthError::NotAllowed { .. })));
+
+        let _: () = call!(canister, "set_caller_allowlist", Some(vec![anonymous])).unwrap();
+        let output = canister
+            .call_as("anonymous", "erc20_balance_of_result", (request,))
+            .unwrap();
+        let (result,): (Result<U256, EthError>,) = candid::decode_args(&output).unwrap();
+        assert!(result.is_ok());
+    }
 }
@@ -2,13 +2,16 @@ use std::process::Command;
 use std::str;
 
 use candid::utils::{encode_args, ArgumentEncoder};
-use candid::IDLArgs;
+use candid::{IDLArgs, Principal};
 use eyre::{ensure, Result, WrapErr};
-use interface::{Network, SetupRequest};
+use interface::{Erc20BalanceOfRequest, Network, PriceList, SetupRequest};
 use temp_dir::TempDir;
 
 const DEFAULT_CONSENSUS_RPC: &str = "https://www.lightclientdata.org";
 const DEFAULT_EXECUTION_RPC: &str = "https://ethereum.publicnode.com";
+const DEFAULT_ECDSA_KEY_NAME: &str = "dfx_test_key";
+pub const DEFAULT_SIWE_DOMAIN: &str = "example.com";
+pub const LEDGER_FEE: u128 = 10_000;
 
 #[derive(Debug)]
 pub struct TestCanister {
@@ -18,6 +21,15 @@ pub struct TestCanister {
 
 impl TestCanister {
     pub fn deploy(name: &str) -> Self {
+        Self::deploy_with(name, &[])
+    }
+
+    /// Deploys the canister with the init argument given in the candid text format.
+    pub fn deploy_with_argument(name: &str, argument: &str) -> Self {
+        Self::deploy_with(name, &["--argument", argument])
+    }
+
+    fn deploy_with(name: &str, deploy_args: &[&str]) -> Self {
         let temp_dir = TempDir::new().unwrap();
 
         // setup the tempdir
@@ -32,18 +44,70 @@ impl TestCanister {
             temp_dir,
             name: name.to_owned(),
         };
-        canister.run_dfx(&["deploy", name]).unwrap();
+        let mut cmd = vec!["deploy", name];
+        cmd.extend(deploy_args);
+        canister.run_dfx(&cmd).unwrap();
 
         canister
     }
 
     pub fn call(&self, method: &str, args: impl ArgumentEncoder) -> Result<Vec<u8>> {
+        self.call_with(&[], &[], method, args)
+    }
+
+    /// Calls the method using the given dfx identity, eg. "anonymous".
+    pub fn call_as(
+        &self,
+        identity: &str,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<Vec<u8>> {
+        self.call_with(&["--identity", identity], &[], method, args)
+    }
+
+    /// Calls the method through the wallet of the dfx identity, attaching the cycles.
+    ///
+    /// The caller seen by the canister is the wallet, see [`TestCanister::wallet_principal`].
+    pub fn call_with_cycles(
+        &self,
+        cycles: u128,
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<Vec<u8>> {
+        let wallet = self.wallet_principal().to_text();
+        let cycles = cycles.to_string();
+        self.call_with(
+            &[],
+            &[
+                "--wallet",
+                wallet.as_str(),
+                "--with-cycles",
+                cycles.as_str(),
+            ],
+            method,
+            args,
+        )
+    }
+
+    fn call_with(
+        &self,
+        dfx_args: &[&str],
+        call_args: &[&str],
+        method: &str,
+        args: impl ArgumentEncoder,
+    ) -> Result<Vec<u8>> {
         // convert arguments into format understood by `dfx`
         let args = encode_args(args).wrap_err("encoding args")?;
         let args = IDLArgs::from_bytes(&args).wrap_err("decoding dfx args")?;
+        let args_str = args.to_string();
+
+        let mut cmd = dfx_args.to_vec();
+        cmd.extend(["canister", "call"]);
+        cmd.extend(call_args);
+        cmd.extend([self.name.as_str(), method, args_str.as_str()]);
 
         let stdout = self
-            .run_dfx(&["canister", "call", &self.name, method, &args.to_string()])
+            .run_dfx(&cmd)
             .wrap_err_with(|| format!("calling '{method} {args}'"))?;
 
         // convert results from the format understood by `dfx`
@@ -56,6 +120,32 @@ impl TestCanister {
         output.to_bytes().wrap_err("encoding to candid")
     }
 
+    /// Principal of the dfx identity used for the calls.
+    pub fn caller_principal(&self) -> Principal {
+        let stdout = self
+            .run_dfx(&["identity", "get-principal"])
+            .expect("getting principal failed");
+        let principal = str::from_utf8(&stdout).expect("decoding principal failed");
+        principal.trim().parse().expect("parsing principal failed")
+    }
+
+    pub fn canister_id(&self) -> Principal {
+        let stdout = self
+            .run_dfx(&["canister", "id", &self.name])
+            .expect("getting canister id failed");
+        let id = str::from_utf8(&stdout).expect("decoding canister id failed");
+        id.trim().parse().expect("parsing canister id failed")
+    }
+
+    /// Principal of the cycles wallet of the dfx identity.
+    pub fn wallet_principal(&self) -> Principal {
+        let stdout = self
+            .run_dfx(&["identity", "get-wallet"])
+            .expect("getting wallet failed");
+        let wallet = str::from_utf8(&stdout).expect("decoding wallet failed");
+        wallet.trim().parse().expect("parsing wallet failed")
+    }
+
     fn remove(&self) {
         self.run_dfx(&["canister", "stop", &self.name])
             .expect("Stopping failed");
@@ -84,14 +174,71 @@ impl Drop for TestCanister {
     }
 }
 
-pub fn setup_ethereum_canister() -> TestCanister {
-    let canister = TestCanister::deploy("ethereum_canister");
-    let request = SetupRequest {
+pub fn setup_request() -> SetupRequest {
+    SetupRequest {
         network: Network::Mainnet,
-        consensus_rpc_url: DEFAULT_CONSENSUS_RPC.to_owned(),
-        execution_rpc_url: DEFAULT_EXECUTION_RPC.to_owned(),
+        consensus_rpc_urls: vec![DEFAULT_CONSENSUS_RPC.to_owned()],
+        execution_rpc_urls: vec![DEFAULT_EXECUTION_RPC.to_owned()],
         checkpoint: None,
-    };
+        checkpoint_sync_urls: None,
+        checkpoint_quorum: None,
+        ecdsa_key_name: Some(DEFAULT_ECDSA_KEY_NAME.to_owned()),
+        siwe_domain: Some(DEFAULT_SIWE_DOMAIN.to_owned()),
+        max_head_age_secs: None,
+        finalized_only: None,
+    }
+}
+
+/// USDT balance of an account holding some, a call that makes a few outcalls.
+pub fn usdt_balance_request() -> Erc20BalanceOfRequest {
+    Erc20BalanceOfRequest {
+        contract: "0xdAC17F958D2ee523a2206206994597C13D831ec7" // usdt
+            .parse()
+            .unwrap(),
+        account: "0xF977814e90dA44bFA03b6295A0616a897441aceC"
+            .parse()
+            .unwrap(),
+    }
+}
+
+/// Prices that the calls above fit in when they are made with 100B cycles attached.
+pub fn sample_price_list() -> PriceList {
+    PriceList {
+        base_fee: 1_000_000,
+        outcall_fee: 2_000_000_000,
+        min_attached_cycles: 50_000_000_000,
+    }
+}
+
+/// Deploys the ICRC-1 ledger with ICRC-2 enabled and mints the tokens to the owner.
+pub fn setup_ledger(owner: Principal, tokens: u128) -> TestCanister {
+    let argument = format!(
+        r#"(variant {{ Init = record {{
+            token_symbol = "TEST";
+            token_name = "Test token";
+            minting_account = record {{ owner = principal "{minter}" }};
+            transfer_fee = {LEDGER_FEE} : nat;
+            metadata = vec {{}};
+            feature_flags = opt record {{ icrc2 = true }};
+            initial_balances = vec {{ record {{ record {{ owner = principal "{owner}" }}; {tokens} : nat }} }};
+            archive_options = record {{
+                num_blocks_to_archive = 1000 : nat64;
+                trigger_threshold = 2000 : nat64;
+                controller_id = principal "{owner}";
+            }};
+        }} }})"#,
+        minter = Principal::anonymous(),
+    );
+
+    TestCanister::deploy_with_argument("icrc1_ledger", &argument)
+}
+
+pub fn setup_ethereum_canister() -> TestCanister {
+    setup_ethereum_canister_with(setup_request())
+}
+
+pub fn setup_ethereum_canister_with(request: SetupRequest) -> TestCanister {
+    let canister = TestCanister::deploy("ethereum_canister");
     let _: () = call!(canister, "setup", request).unwrap();
     canister
 }
@@ -0,0 +1,62 @@
+use candid::CandidType;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::U256;
+
+/// Reason of a failed request, returned by the `*_result` methods.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize, Error)]
+pub enum EthError {
+    /// The client is not started, the canister needs to be set up.
+    #[error("Client not started")]
+    NotInitialized,
+    /// The verified header the reads are served from is older than the configured maximum.
+    #[error("Stale head: latest verified header is {head_age_secs}s old, maximum allowed age is {max_head_age_secs}s")]
+    Stale {
+        head_age_secs: u64,
+        max_head_age_secs: u64,
+    },
+    /// The nodes could not be reached or returned an invalid response. Worth retrying.
+    #[error("Rpc unavailable: {message}")]
+    RpcUnavailable { message: String },
+    /// The node returned data that didn't match the verified state root.
+    #[error("Proof verification failed: {message}")]
+    ProofVerificationFailed { message: String },
+    /// The call was executed and reverted with the given data.
+    #[error("Execution reverted")]
+    ExecutionReverted {
+        data: Vec<u8>,
+        /// Decoded revert data, if the error is known.
+        reason: Option<RevertReason>,
+    },
+    #[error("Invalid input: {message}")]
+    InvalidInput { message: String },
+    #[error("Out of cycles")]
+    OutOfCycles,
+    /// The tokens approved for the canister couldn't be transferred to pay for the call.
+    #[error("Payment failed: {message}")]
+    PaymentFailed { message: String },
+    /// The caller used up its quota, see `get_quota`.
+    #[error("Quota exceeded, retry after {retry_after_secs} seconds")]
+    QuotaExceeded { retry_after_secs: u64 },
+    /// The caller or the contract is not in the allowlist.
+    #[error("Not allowed: {message}")]
+    NotAllowed { message: String },
+    #[error("{message}")]
+    Other { message: String },
+}
+
+/// Decoded data of a reverted call.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub enum RevertReason {
+    /// `Error(string)` raised by `require` and `revert` with a message.
+    Error { message: String },
+    /// `Panic(uint256)` raised by failed assertions, arithmetic errors and similar.
+    Panic { code: U256, description: String },
+    /// A custom error declared in the ABI of the contract.
+    Custom {
+        name: String,
+        /// Values of the arguments formatted as strings.
+        args: Vec<String>,
+    },
+}
@@ -0,0 +1,64 @@
+use std::str::FromStr;
+
+use candid::types::{Compound, Serializer, Type};
+use candid::CandidType;
+use ethers_core::types::H256 as EthersH256;
+use serde::Deserialize;
+
+/// A 32 bytes hash, represented as a hex string in Candid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct H256(EthersH256);
+
+impl CandidType for H256 {
+    fn _ty() -> Type {
+        <String as CandidType>::ty()
+    }
+
+    fn idl_serialize<S>(&self, serializer: S) -> Result<(), S::Error>
+    where
+        S: Serializer,
+    {
+        let s = format!("{:?}", &self.0);
+        let mut ser = serializer.serialize_struct()?;
+        Compound::serialize_element(&mut ser, &s)?;
+        Ok(())
+    }
+}
+
+impl<'de> Deserialize<'de> for H256 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let hash = s.parse::<EthersH256>().map_err(serde::de::Error::custom)?;
+        Ok(H256(hash))
+    }
+}
+
+impl From<EthersH256> for H256 {
+    fn from(value: EthersH256) -> Self {
+        H256(value)
+    }
+}
+
+impl From<H256> for EthersH256 {
+    fn from(value: H256) -> Self {
+        value.0
+    }
+}
+
+impl From<[u8; 32]> for H256 {
+    fn from(value: [u8; 32]) -> Self {
+        H256(value.into())
+    }
+}
+
+impl FromStr for H256 {
+    type Err = <EthersH256 as FromStr>::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hash: EthersH256 = s.parse()?;
+        Ok(Self(hash))
+    }
+}
@@ -1,20 +1,45 @@
-use candid::CandidType;
+use candid::{CandidType, Principal};
 use serde::Deserialize;
 
 mod address;
+mod error;
+mod h256;
 mod network;
+mod signature;
+mod token_gate;
 mod u256;
 
 pub use address::Address;
-pub use network::{BadNetwork, Network};
+pub use error::{EthError, RevertReason};
+pub use h256::H256;
+pub use network::{BadNetwork, CustomNetwork, Fork, Network};
+pub use signature::Signature;
+pub use token_gate::TokenGateRule;
 pub use u256::{U256ConvertError, U256};
 
 #[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
 pub struct SetupRequest {
     pub network: Network,
-    pub consensus_rpc_url: String,
-    pub execution_rpc_url: String,
+    /// Tried in order, failing over to the next one when the client stops syncing.
+    pub consensus_rpc_urls: Vec<String>,
+    /// Tried in order, failing over to the next one when a request fails.
+    pub execution_rpc_urls: Vec<String>,
     pub checkpoint: Option<String>,
+    /// Checkpoint sync providers queried along with the consensus nodes when no checkpoint is given.
+    pub checkpoint_sync_urls: Option<Vec<String>>,
+    /// Number of sources that must agree on the checkpoint. Defaults to the majority.
+    pub checkpoint_quorum: Option<u32>,
+    pub ecdsa_key_name: Option<String>,
+    pub siwe_domain: Option<String>,
+    pub max_head_age_secs: Option<u64>,
+    /// Serve reads from the finalized block only, never exposing optimistic data.
+    pub finalized_only: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct ReconfigureRequest {
+    pub consensus_rpc_urls: Option<Vec<String>>,
+    pub execution_rpc_urls: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
@@ -29,6 +54,20 @@ pub struct Erc721OwnerOfRequest {
     pub token_id: U256,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct RegisterAbiRequest {
+    pub contract: Address,
+    /// JSON ABI of the contract, only its errors are used.
+    pub abi_json: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct Erc1271IsValidSignatureRequest {
+    pub contract: Address,
+    pub hash: H256,
+    pub signature: Vec<u8>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
 pub struct EstimateGasRequest {
     pub from: Option<Address>,
@@ -38,3 +77,139 @@ pub struct EstimateGasRequest {
     pub value: Option<U256>,
     pub data: Option<Vec<u8>>,
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct SignTypedDataRequest {
+    pub derivation_path: Vec<Vec<u8>>,
+    pub typed_data_json: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct RecoverAddressRequest {
+    pub message_hash: H256,
+    pub signature: Signature,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct VerifyPersonalMessageRequest {
+    pub address: Address,
+    pub message: String,
+    pub signature: Signature,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct SiweLoginRequest {
+    pub message: String,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct CheckTokenGateRequest {
+    pub principal: Principal,
+    pub rule: TokenGateRule,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct SyncStatus {
+    pub optimistic_slot: u64,
+    pub optimistic_block: u64,
+    pub finalized_slot: u64,
+    pub finalized_block: u64,
+    /// Time of the last observed advance of the head in nanoseconds since the unix epoch.
+    pub last_advance_timestamp: Option<u64>,
+    /// Number of consecutive sync polls during which the head didn't advance.
+    pub consecutive_failures: u32,
+    pub sync_committee_period: u64,
+    pub checkpoint: Option<String>,
+    /// Whether the reads are served from the finalized block only.
+    pub finalized_only: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct RpcEndpointStats {
+    pub url: String,
+    /// Whether the client currently uses this endpoint.
+    pub current: bool,
+    pub requests: u64,
+    pub errors: u64,
+    pub average_latency_ms: u64,
+    /// Time until which the endpoint is skipped after returning invalid proofs, in nanoseconds since the unix epoch.
+    pub demoted_until: Option<u64>,
+}
+
+/// Cycles charged for the update calls, the attached cycles that are not charged are refunded.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct PriceList {
+    /// Charged for every call on top of the https outcalls.
+    pub base_fee: u128,
+    /// Charged for each https outcall made by the call.
+    pub outcall_fee: u128,
+    /// Calls with less cycles attached are rejected before performing any outcall,
+    /// at least the base fee and one outcall.
+    pub min_attached_cycles: u128,
+}
+
+/// ICRC-2 ledger used to pay for the calls that don't have cycles attached.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct LedgerConfig {
+    pub ledger: Principal,
+    /// Price of a trillion cycles in the smallest units of the token.
+    pub tokens_per_trillion_cycles: u128,
+    /// Amount pulled with `icrc2_transfer_from` when the prepaid balance is not enough,
+    /// the part that is not spent is kept for the next calls.
+    pub transfer_amount: u128,
+}
+
+/// Transfer of the tokens collected from the callers out of the canister's account.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct WithdrawTokensRequest {
+    pub to: Principal,
+    /// The ledger fee is paid on top of the amount.
+    pub amount: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct EstimateCallCostRequest {
+    pub contract: Address,
+    /// ABI encoded call data, including the selector.
+    pub data: Vec<u8>,
+}
+
+/// Cost of a call measured by executing it against the current head.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct CallCost {
+    /// Approximate number of https outcalls, derived from the cycles spent on them
+    /// with the average cost of an outcall, as helios doesn't report the outcalls it makes.
+    pub estimated_outcalls: u32,
+    pub outcalls_cycles: u128,
+    /// Cycles to attach to the call with the current price list, `None` if the calls are free.
+    pub cycles_to_attach: Option<u128>,
+}
+
+/// Limits of the update calls of the principals assigned to the tier.
+///
+/// The tier named `default` applies to the principals without a tier assigned.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct QuotaTier {
+    pub name: String,
+    pub requests_per_minute: u32,
+    /// Estimated from the cycles spent by the calls.
+    pub outcalls_per_hour: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct AssignQuotaTierRequest {
+    pub principal: Principal,
+    /// `None` moves the principal back to the default tier.
+    pub tier: Option<String>,
+}
+
+/// Limits of the principal and how much of them it used up.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct Quota {
+    pub tier: String,
+    pub requests_per_minute: u32,
+    pub outcalls_per_hour: u32,
+    pub requests_in_current_minute: u32,
+    pub outcalls_in_current_hour: u32,
+}
@@ -5,10 +5,48 @@ use candid::CandidType;
 use serde::Deserialize;
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Deserialize)]
+use crate::H256;
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
 pub enum Network {
     Mainnet,
     Goerli,
+    Sepolia,
+    Holesky,
+    Custom(CustomNetwork),
+}
+
+/// Configuration of a network that is not known to the canister, eg. a local devnet.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct CustomNetwork {
+    pub chain_id: u64,
+    /// Beacon chain genesis time in seconds since the unix epoch.
+    pub genesis_time: u64,
+    pub genesis_validators_root: H256,
+    pub genesis: Fork,
+    pub altair: Fork,
+    pub bellatrix: Fork,
+    pub capella: Fork,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub struct Fork {
+    pub epoch: u64,
+    /// Hex encoded 4 bytes fork version, eg. "0x90000069".
+    pub version: String,
+}
+
+impl Network {
+    /// EIP-155 chain id of the network.
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Network::Mainnet => 1,
+            Network::Goerli => 5,
+            Network::Sepolia => 11_155_111,
+            Network::Holesky => 17_000,
+            Network::Custom(custom) => custom.chain_id,
+        }
+    }
 }
 
 impl Display for Network {
@@ -16,6 +54,9 @@ impl Display for Network {
         match self {
             Network::Mainnet => f.write_str("Mainnet"),
             Network::Goerli => f.write_str("Goerli"),
+            Network::Sepolia => f.write_str("Sepolia"),
+            Network::Holesky => f.write_str("Holesky"),
+            Network::Custom(custom) => write!(f, "Custom (chain id {})", custom.chain_id),
         }
     }
 }
@@ -31,6 +72,8 @@ impl FromStr for Network {
         match s {
             "Mainnet" | "mainnet" => Ok(Network::Mainnet),
             "Goerli" | "goerli" => Ok(Network::Goerli),
+            "Sepolia" | "sepolia" => Ok(Network::Sepolia),
+            "Holesky" | "holesky" => Ok(Network::Holesky),
             _ => Err(BadNetwork),
         }
     }
@@ -0,0 +1,33 @@
+use candid::CandidType;
+use ethers_core::types::Signature as EthersSignature;
+use serde::Deserialize;
+
+use crate::U256;
+
+/// An Ethereum ECDSA signature with the `v` value in 'Electrum' notation (27 or 28).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CandidType, Deserialize)]
+pub struct Signature {
+    pub r: U256,
+    pub s: U256,
+    pub v: u64,
+}
+
+impl From<EthersSignature> for Signature {
+    fn from(value: EthersSignature) -> Self {
+        Signature {
+            r: value.r.into(),
+            s: value.s.into(),
+            v: value.v,
+        }
+    }
+}
+
+impl From<Signature> for EthersSignature {
+    fn from(value: Signature) -> Self {
+        EthersSignature {
+            r: value.r.into(),
+            s: value.s.into(),
+            v: value.v,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use candid::CandidType;
+use serde::Deserialize;
+
+use crate::{Address, U256};
+
+/// A condition on the token holdings of an Ethereum account.
+#[derive(Debug, Clone, PartialEq, Eq, CandidType, Deserialize)]
+pub enum TokenGateRule {
+    /// ERC-20 balance is at least `min_balance`.
+    Erc20MinBalance {
+        contract: Address,
+        min_balance: U256,
+    },
+    /// Owns any token of the ERC-721 collection.
+    Erc721OwnsAny { contract: Address },
+    /// Owns the specific ERC-721 token.
+    Erc721OwnsToken { contract: Address, token_id: U256 },
+    /// ERC-1155 balance of `token_id` is at least `min_balance`.
+    Erc1155MinBalance {
+        contract: Address,
+        token_id: U256,
+        min_balance: U256,
+    },
+    /// All of the rules are satisfied.
+    All(Vec<TokenGateRule>),
+    /// At least one of the rules is satisfied.
+    Any(Vec<TokenGateRule>),
+}